@@ -0,0 +1,111 @@
+use candidate_selection::{select, select_lazy, ArrayVec, Candidate, Normalized};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// A candidate with a submodular combined score: the probability that at least one of the
+/// candidates succeeds, given their individual scores as success rates. Like the `score_many` of
+/// `indexer_selection`, this sorts the candidates by latency to sum the probabilities of each one
+/// returning the first success, so that its cost is comparable.
+struct Coverage {
+    id: u16,
+    latency_ms: u16,
+    fee: Normalized,
+    score: Normalized,
+}
+
+impl Candidate for Coverage {
+    type Id = u16;
+    const SUBMODULAR: bool = true;
+    fn id(&self) -> &Self::Id {
+        &self.id
+    }
+    fn fee(&self) -> Normalized {
+        self.fee
+    }
+    fn score(&self) -> Normalized {
+        self.score
+    }
+    fn score_many<const LIMIT: usize>(candidates: &[&Self]) -> Normalized {
+        Self::score_many_dyn(candidates)
+    }
+    fn score_many_dyn(candidates: &[&Self]) -> Normalized {
+        let mut candidates = candidates.to_vec();
+        candidates.sort_unstable_by_key(|c| c.latency_ms);
+        let mut all_failed = 1.0;
+        let mut first_success = 0.0;
+        for candidate in candidates {
+            first_success += all_failed * candidate.score.as_f64();
+            all_failed *= 1.0 - candidate.score.as_f64();
+        }
+        Normalized::new(first_success.min(1.0)).unwrap()
+    }
+}
+
+/// Equivalent to `Coverage`, without the submodular claim. So `select_lazy` falls back to the
+/// exact loop of `select`.
+struct NotSubmodular(Coverage);
+
+impl Candidate for NotSubmodular {
+    type Id = u16;
+    fn id(&self) -> &Self::Id {
+        self.0.id()
+    }
+    fn fee(&self) -> Normalized {
+        self.0.fee()
+    }
+    fn score(&self) -> Normalized {
+        self.0.score()
+    }
+    fn score_many<const LIMIT: usize>(candidates: &[&Self]) -> Normalized {
+        Self::score_many_dyn(candidates)
+    }
+    fn score_many_dyn(candidates: &[&Self]) -> Normalized {
+        let candidates: Vec<&Coverage> = candidates.iter().map(|c| &c.0).collect();
+        Coverage::score_many_dyn(&candidates)
+    }
+}
+
+fn candidates(count: u16) -> Vec<Coverage> {
+    (0..count)
+        .map(|i| Coverage {
+            id: i,
+            latency_ms: (i * 37) % 500,
+            fee: Normalized::new((i % 7) as f64 / 10.0).unwrap(),
+            score: Normalized::new((i % 13) as f64 / 13.0).unwrap(),
+        })
+        .collect()
+}
+
+fn bench_select(c: &mut Criterion) {
+    let mut group = c.benchmark_group("submodular");
+    for count in [50, 500] {
+        let candidates = candidates(count);
+        group.bench_with_input(BenchmarkId::new("select", count), &candidates, |b, c| {
+            b.iter(|| select::<_, 3>(c))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("select_lazy", count),
+            &candidates,
+            |b, c| b.iter(|| select_lazy::<_, 3>(c)),
+        );
+    }
+    group.finish();
+
+    // `select_lazy` must not be faster here, since it falls back to the exact loop.
+    let mut group = c.benchmark_group("not_submodular");
+    for count in [50, 500] {
+        let candidates: Vec<NotSubmodular> =
+            candidates(count).into_iter().map(NotSubmodular).collect();
+        group.bench_with_input(BenchmarkId::new("select", count), &candidates, |b, c| {
+            b.iter(|| -> ArrayVec<&NotSubmodular, 3> { select(c) })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("select_lazy", count),
+            &candidates,
+            |b, c| b.iter(|| -> ArrayVec<&NotSubmodular, 3> { select_lazy(c) }),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_select);
+criterion_main!(benches);
@@ -0,0 +1,87 @@
+use arrayvec::ArrayVec;
+
+use crate::{greedy, no_tie_break, Candidate, Normalized, SelectionOptions};
+
+/// The result of [`select_explained`], including the trace of decisions made by the selection.
+#[derive(Debug)]
+pub struct Explained<'c, Candidate, Id, const LIMIT: usize> {
+    pub selected: ArrayVec<&'c Candidate, LIMIT>,
+    /// One entry per round of the greedy selection, in order.
+    pub rounds: Vec<Round<Id>>,
+    /// Why the selection stopped after the last round.
+    pub stop: StopReason,
+}
+
+/// A round of the greedy selection, in which at most one candidate is selected.
+#[derive(Debug)]
+pub struct Round<Id> {
+    /// Every candidate considered in this round, in the order they were provided.
+    pub evaluations: Vec<Evaluation<Id>>,
+    /// The candidate selected in this round, if any.
+    pub selected: Option<Id>,
+}
+
+#[derive(Debug)]
+pub struct Evaluation<Id> {
+    pub id: Id,
+    /// The increase in the combined score of the selection from adding this candidate.
+    pub marginal_score: f64,
+    pub fee: Normalized,
+    /// The marginal score divided by the fee, as adjusted by [`SelectionOptions::DEFAULT`]. The
+    /// candidate with the highest value is selected, as long as its marginal score is positive.
+    pub fee_adjusted_score: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The selection limit was reached.
+    LimitReached,
+    /// All candidates were either selected, or would exceed the fee budget if selected.
+    NoCandidatesRemaining,
+    /// No remaining candidate would increase the combined score of the selection.
+    NoPositiveMarginalScore,
+}
+
+/// Equivalent to [`select`](crate::select), but also returns the trace of decisions made in each
+/// round of the selection. When `fee_budget` is set, the combined fee of the selected candidates is
+/// checked against it as described in [`SelectionOptions::fee_budget`].
+pub fn select_explained<Candidate, const LIMIT: usize>(
+    candidates: &[Candidate],
+    fee_budget: Option<u128>,
+) -> Explained<'_, Candidate, &Candidate::Id, LIMIT>
+where
+    Candidate: crate::Candidate,
+{
+    assert!(LIMIT > 0);
+    let mut rounds = Vec::new();
+    let (selected, stop) = greedy(
+        candidates,
+        LIMIT,
+        ArrayVec::new(),
+        &SelectionOptions {
+            fee_budget,
+            ..SelectionOptions::DEFAULT
+        },
+        no_tie_break,
+        Some(&mut rounds),
+    );
+    Explained {
+        selected,
+        rounds,
+        stop,
+    }
+}
+
+impl<'c, Id> Evaluation<&'c Id> {
+    pub(crate) fn new<C>(candidate: &'c C, marginal_score: f64, fee_adjusted_score: f64) -> Self
+    where
+        C: Candidate<Id = Id>,
+    {
+        Self {
+            id: candidate.id(),
+            marginal_score,
+            fee: candidate.fee(),
+            fee_adjusted_score,
+        }
+    }
+}
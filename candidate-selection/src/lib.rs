@@ -1,62 +1,225 @@
+mod explain;
 pub mod num;
+mod optimal;
+mod options;
 #[cfg(test)]
 mod test;
 
+use std::{
+    collections::BinaryHeap,
+    hash::{Hash, Hasher},
+};
+
 pub use arrayvec::ArrayVec;
 use ordered_float::NotNan;
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
 
-pub use crate::num::Normalized;
+pub use crate::{explain::*, num::Normalized, optimal::*, options::*};
 
 pub trait Candidate {
     type Id: Eq + Ord;
-    fn id(&self) -> Self::Id;
+    /// Whether `score_many` is submodular, i.e. the marginal score of adding a candidate to a set
+    /// never increases as the set grows. This enables the lazy evaluation in [`select_lazy`].
+    const SUBMODULAR: bool = false;
+    fn id(&self) -> &Self::Id;
+    /// A key for the group of candidates that this candidate belongs to, such as a hash of its
+    /// operator, region, or ASN. See [`SelectionOptions::max_per_group`].
+    fn group(&self) -> Option<u64> {
+        None
+    }
+    /// The fee of the candidate, as a fraction of the budget. The sum of fees for selected
+    /// candidates will not exceed 1.
     fn fee(&self) -> Normalized;
+    /// The fee of the candidate in integer units, such as GRT wei. See
+    /// [`SelectionOptions::fee_budget`].
+    fn fee_units(&self) -> Option<u128> {
+        None
+    }
     fn score(&self) -> Normalized;
     fn score_many<const LIMIT: usize>(candidates: &[&Self]) -> Normalized;
+    /// Equivalent to `score_many`, for sets of candidates with a size only known at runtime.
+    ///
+    /// The default implementation calls `score_many` with the smallest `LIMIT` in 2, 4, 8, ...,
+    /// 256 that fits the candidates.
+    ///
+    /// # Panics
+    /// If the default implementation is given more than 256 candidates.
+    fn score_many_dyn(candidates: &[&Self]) -> Normalized {
+        match candidates.len() {
+            0..=2 => Self::score_many::<2>(candidates),
+            3..=4 => Self::score_many::<4>(candidates),
+            5..=8 => Self::score_many::<8>(candidates),
+            9..=16 => Self::score_many::<16>(candidates),
+            17..=32 => Self::score_many::<32>(candidates),
+            33..=64 => Self::score_many::<64>(candidates),
+            65..=128 => Self::score_many::<128>(candidates),
+            129..=256 => Self::score_many::<256>(candidates),
+            n => panic!("score_many_dyn is not implemented for {n} candidates"),
+        }
+    }
+    /// An upper bound on the combined score of any set of candidates containing `candidates`. This
+    /// is used to prune the search in [`select_optimal`], so a tighter bound makes it faster.
+    fn score_bound<const LIMIT: usize>(_candidates: &[&Self]) -> Normalized {
+        Normalized::ONE
+    }
 }
 
 /// Select up to `LIMIT` of the provided candidates.
 ///
 /// At least one candidate will be selected, as long as there is at least one candidate with an
-/// individual score greater than 0.
-pub fn select<'c, Candidate, const LIMIT: usize>(
+/// individual score greater than 0. The combined fee of the selected candidates will not exceed the
+/// budget.
+pub fn select<Candidate, const LIMIT: usize>(
+    candidates: &[Candidate],
+) -> ArrayVec<&Candidate, LIMIT>
+where
+    Candidate: crate::Candidate,
+{
+    assert!(LIMIT > 0);
+    greedy(
+        candidates,
+        LIMIT,
+        ArrayVec::new(),
+        &SelectionOptions::DEFAULT,
+        no_tie_break,
+        None,
+    )
+    .0
+}
+
+/// Equivalent to [`select`], but with the given `options`. See [`SelectionOptions`].
+pub fn select_with_options<'c, Candidate, const LIMIT: usize>(
     candidates: &'c [Candidate],
-) -> ArrayVec<&'c Candidate, LIMIT>
+    options: &SelectionOptions<Candidate::Id>,
+) -> Result<ArrayVec<&'c Candidate, LIMIT>, InvalidSelectionOptions>
 where
     Candidate: crate::Candidate,
+    Candidate::Id: Hash,
 {
     assert!(LIMIT > 0);
+    options.validate()?;
+    let tie_break = |candidate: &Candidate| tie_break_rank(options.tie_break_seed, candidate);
+    Ok(greedy(candidates, LIMIT, ArrayVec::new(), options, tie_break, None).0)
+}
 
-    let marginal_score = |current_score: Normalized,
-                          selected: &ArrayVec<&'c Candidate, LIMIT>,
-                          candidate: &'c Candidate| {
-        let potential_score = if selected.is_empty() {
-            Candidate::score(candidate)
-        } else {
-            let mut buf = selected.clone();
-            buf.push(candidate);
-            Candidate::score_many::<LIMIT>(&buf)
-        };
-        NotNan::new(potential_score.as_f64() - current_score.as_f64()).unwrap()
-    };
+/// Equivalent to [`select_with_options`], but also returns an error if fewer than
+/// `options.min_selected` candidates are selected.
+pub fn try_select_with_options<'c, Candidate, const LIMIT: usize>(
+    candidates: &'c [Candidate],
+    options: &SelectionOptions<Candidate::Id>,
+) -> Result<ArrayVec<&'c Candidate, LIMIT>, TrySelectError<'c, Candidate, LIMIT>>
+where
+    Candidate: crate::Candidate,
+    Candidate::Id: Hash,
+{
+    assert!(LIMIT > 0);
+    options.validate()?;
+    let tie_break = |candidate: &Candidate| tie_break_rank(options.tie_break_seed, candidate);
+    let (selected, stop) = greedy(candidates, LIMIT, ArrayVec::new(), options, tie_break, None);
+    if selected.len() < options.min_selected {
+        return Err(TrySelectError::BelowMinimum(BelowMinimum {
+            selected,
+            min_selected: options.min_selected,
+            stop,
+        }));
+    }
+    Ok(selected)
+}
+
+/// Select up to `limit` of the provided candidates. This is equivalent to [`select`], for when the
+/// limit is only known at runtime. When `fee_budget` is set, the combined fee of the selected
+/// candidates is checked against it as described in [`SelectionOptions::fee_budget`].
+///
+/// # Panics
+/// If `limit` is 0. And, when `Candidate` uses the default implementation of
+/// [`Candidate::score_many_dyn`], if `limit` is over 256 and the selection grows past 256
+/// candidates.
+pub fn select_dyn<Candidate>(
+    candidates: &[Candidate],
+    limit: usize,
+    fee_budget: Option<u128>,
+) -> Vec<&Candidate>
+where
+    Candidate: crate::Candidate,
+{
+    assert!(limit > 0);
+    greedy(
+        candidates,
+        limit,
+        Vec::with_capacity(limit),
+        &SelectionOptions {
+            fee_budget,
+            ..SelectionOptions::DEFAULT
+        },
+        no_tie_break,
+        None,
+    )
+    .0
+}
+
+/// Select up to `LIMIT` of the provided candidates, returning the same selections as [`select`].
+///
+/// When `Candidate::SUBMODULAR` is set, marginal scores from earlier rounds are upper bounds on the
+/// current marginal scores. So candidates are kept in a priority queue of (possibly stale) marginal
+/// scores, and only the candidate at the top of the queue is re-evaluated until a candidate that is
+/// up to date reaches the top. This typically reduces the number of `score_many` calls from
+/// `LIMIT * candidates.len()` to about `candidates.len()`.
+///
+/// This falls back to [`select`] when `Candidate::SUBMODULAR` is not set. As a safeguard against an
+/// incorrect `SUBMODULAR` claim, it also falls back when a re-evaluated marginal score is found to
+/// exceed its previous value. But violations on candidates that are never re-evaluated can't be
+/// detected, so the same selections as `select` are only guaranteed for submodular scores.
+pub fn select_lazy<Candidate, const LIMIT: usize>(
+    candidates: &[Candidate],
+) -> ArrayVec<&Candidate, LIMIT>
+where
+    Candidate: crate::Candidate,
+{
+    assert!(LIMIT > 0);
+    if !Candidate::SUBMODULAR {
+        return select(candidates);
+    }
 
+    let options = &SelectionOptions::DEFAULT;
     let mut selected: ArrayVec<&Candidate, LIMIT> = Default::default();
+    // Entries are (fee-adjusted marginal score, candidate index, round evaluated). Ties on the
+    // marginal score are broken by the higher index, matching `select`.
+    let mut queue: BinaryHeap<(NotNan<f64>, usize, usize)> = candidates
+        .iter()
+        .enumerate()
+        .map(|(index, c)| {
+            let marginal_score = marginal_score(Normalized::ZERO, &mut selected, c);
+            (fee_adjusted(marginal_score, c, options), index, 0)
+        })
+        .collect();
+
     while selected.len() < LIMIT {
-        let current_score = match selected.len() {
-            0 => Normalized::ZERO,
-            1 => Candidate::score(selected[0]),
-            _ => Candidate::score_many::<LIMIT>(&selected),
+        let round = selected.len();
+        let current_score = selected.score();
+        let selection = loop {
+            let Some((bound, index, evaluated)) = queue.pop() else {
+                break None;
+            };
+            let candidate = &candidates[index];
+            // The selection only grows, so an ineligible candidate never becomes eligible again.
+            if !is_eligible(&selected, candidate, options) {
+                continue;
+            }
+            if evaluated == round {
+                break Some((candidate, bound));
+            }
+            let marginal_score = fee_adjusted(
+                marginal_score(current_score, &mut selected, candidate),
+                candidate,
+                options,
+            );
+            if marginal_score > bound {
+                return select(candidates);
+            }
+            queue.push((marginal_score, index, round));
         };
-        let selection = candidates
-            .iter()
-            .filter(|c| selected.iter().all(|s| s.id() != c.id()))
-            .map(|c| (c, marginal_score(current_score, &selected, c)))
-            .max_by_key(|(c, marginal_score)| {
-                marginal_score / NotNan::new(c.fee().as_f64().max(0.01)).unwrap()
-            })
-            .filter(|(_, marginal_score)| *marginal_score.as_ref() > 0.0);
         match selection {
-            Some((selection, _)) => {
+            Some((selection, marginal_score)) if *marginal_score > 0.0 => {
                 selected.push(selection);
             }
             _ => break,
@@ -64,3 +227,335 @@ where
     }
     selected
 }
+
+/// Storage for selected candidates, with a capacity fixed either at compile time or at runtime.
+trait Selection<'c, Candidate: 'c>: std::ops::Deref<Target = [&'c Candidate]> {
+    fn push(&mut self, candidate: &'c Candidate);
+    fn pop(&mut self) -> Option<&'c Candidate>;
+    /// The combined score of the selected candidates.
+    fn score(&self) -> Normalized;
+}
+
+impl<'c, Candidate, const LIMIT: usize> Selection<'c, Candidate> for ArrayVec<&'c Candidate, LIMIT>
+where
+    Candidate: crate::Candidate,
+{
+    fn push(&mut self, candidate: &'c Candidate) {
+        ArrayVec::push(self, candidate)
+    }
+    fn pop(&mut self) -> Option<&'c Candidate> {
+        ArrayVec::pop(self)
+    }
+    fn score(&self) -> Normalized {
+        match self.len() {
+            0 => Normalized::ZERO,
+            1 => Candidate::score(self[0]),
+            _ => Candidate::score_many::<LIMIT>(self),
+        }
+    }
+}
+
+impl<'c, Candidate> Selection<'c, Candidate> for Vec<&'c Candidate>
+where
+    Candidate: crate::Candidate,
+{
+    fn push(&mut self, candidate: &'c Candidate) {
+        Vec::push(self, candidate)
+    }
+    fn pop(&mut self) -> Option<&'c Candidate> {
+        Vec::pop(self)
+    }
+    fn score(&self) -> Normalized {
+        match self.len() {
+            0 => Normalized::ZERO,
+            1 => Candidate::score(self[0]),
+            _ => Candidate::score_many_dyn(self),
+        }
+    }
+}
+
+/// Select candidates greedily. Ties between candidates with the same fee-adjusted marginal score
+/// go to the candidate with the highest `tie_break` rank, and then to the last of them.
+fn greedy<'c, Candidate, S>(
+    candidates: &'c [Candidate],
+    limit: usize,
+    mut selected: S,
+    options: &SelectionOptions<Candidate::Id>,
+    tie_break: impl Fn(&Candidate) -> u64,
+    mut trace: Option<&mut Vec<Round<&'c Candidate::Id>>>,
+) -> (S, StopReason)
+where
+    Candidate: crate::Candidate,
+    S: Selection<'c, Candidate>,
+{
+    let mut rng = options
+        .softmax
+        .as_ref()
+        .map(|softmax| (StdRng::seed_from_u64(softmax.seed), softmax.temperature));
+    // (candidate, marginal score, fee-adjusted marginal score) for candidates with a positive
+    // marginal score
+    let mut positive: Vec<(&Candidate, NotNan<f64>, NotNan<f64>)> = Vec::new();
+    if !options.pinned.is_empty() {
+        for candidate in candidates {
+            let id = candidate.id();
+            if (selected.len() < limit)
+                && options.pinned.contains(id)
+                && !options.excluded.contains(id)
+                && is_eligible(&selected, candidate, options)
+                && within_group_cap(&selected, candidate, options)
+            {
+                selected.push(candidate);
+            }
+        }
+    }
+    loop {
+        if selected.len() >= limit {
+            return (selected, StopReason::LimitReached);
+        }
+        let current_score = selected.score();
+        let mut evaluations = Vec::new();
+        positive.clear();
+        // (candidate, marginal score, fee-adjusted marginal score, tie-break rank)
+        let mut selection: Option<(&Candidate, NotNan<f64>, NotNan<f64>, u64)> = None;
+        // The candidate with the best fee-adjusted individual score, which is selected regardless of
+        // its marginal score while there are fewer than `options.min_selected` selections.
+        // Candidates with an individual score of zero are never selected this way.
+        let mut fallback: Option<(&Candidate, NotNan<f64>, u64)> = None;
+        let below_min = selected.len() < options.min_selected;
+        for candidate in candidates {
+            if options.excluded.contains(candidate.id())
+                || !is_eligible(&selected, candidate, options)
+                || !within_group_cap(&selected, candidate, options)
+            {
+                continue;
+            }
+            let marginal_score = marginal_score(current_score, &mut selected, candidate);
+            let fee_adjusted = fee_adjusted(marginal_score, candidate, options);
+            if trace.is_some() {
+                evaluations.push(Evaluation::new(candidate, *marginal_score, *fee_adjusted));
+            }
+            if rng.is_some() && (*marginal_score > 0.0) {
+                positive.push((candidate, marginal_score, fee_adjusted));
+            }
+            let rank = tie_break(candidate);
+            // On ties of equal rank, select the last candidate.
+            if selection.is_none_or(|(_, _, max, max_rank)| (fee_adjusted, rank) >= (max, max_rank))
+            {
+                selection = Some((candidate, marginal_score, fee_adjusted, rank));
+            }
+            if below_min && (candidate.score() > Normalized::ZERO) {
+                let individual =
+                    crate::fee_adjusted(candidate.score().as_inner(), candidate, options);
+                if fallback.is_none_or(|(_, max, max_rank)| (individual, rank) >= (max, max_rank)) {
+                    fallback = Some((candidate, individual, rank));
+                }
+            }
+        }
+        if let Some((rng, temperature)) = rng.as_mut() {
+            if !positive.is_empty() {
+                let (candidate, marginal_score, fee_adjusted) =
+                    sample_softmax(&positive, *temperature, rng);
+                selection = Some((candidate, marginal_score, fee_adjusted, 0));
+            }
+        }
+        let selection = match selection {
+            Some((selection, marginal_score, _, _)) if *marginal_score > 0.0 => Ok(selection),
+            Some(_) => fallback
+                .map(|(fallback, _, _)| fallback)
+                .ok_or(StopReason::NoPositiveMarginalScore),
+            None => Err(StopReason::NoCandidatesRemaining),
+        };
+        if let Some(trace) = trace.as_mut() {
+            trace.push(Round {
+                evaluations,
+                selected: selection.ok().map(|s| s.id()),
+            });
+        }
+        match selection {
+            Ok(selection) => selected.push(selection),
+            Err(stop) => return (selected, stop),
+        };
+    }
+}
+
+/// Sample one of the `positive` candidates, with probabilities given by the softmax of their
+/// fee-adjusted marginal scores at the given `temperature`.
+fn sample_softmax<'c, Candidate>(
+    positive: &[(&'c Candidate, NotNan<f64>, NotNan<f64>)],
+    temperature: f64,
+    rng: &mut StdRng,
+) -> (&'c Candidate, NotNan<f64>, NotNan<f64>) {
+    // Subtracting the maximum keeps the weights in (0, 1], with a total of at least 1.
+    let max = positive.iter().map(|(_, _, s)| *s).max().unwrap();
+    let weight = |fee_adjusted: NotNan<f64>| ((*fee_adjusted - *max) / temperature).exp();
+    let total = positive.iter().map(|(_, _, s)| weight(*s)).sum::<f64>();
+    let mut sample = rng.gen_range(0.0..total);
+    for entry in positive {
+        sample -= weight(entry.2);
+        if sample < 0.0 {
+            return *entry;
+        }
+    }
+    *positive.last().unwrap()
+}
+
+/// A pseudorandom rank of `candidate` for the given `seed`, used to break ties. The rank is stable
+/// across Rust releases & platforms, as long as the `Hash` implementation of the ID is. Without a
+/// seed, all candidates have the same rank.
+fn tie_break_rank<Candidate>(seed: Option<u64>, candidate: &Candidate) -> u64
+where
+    Candidate: crate::Candidate,
+    Candidate::Id: Hash,
+{
+    let Some(seed) = seed else {
+        return 0;
+    };
+    let mut hasher = StableHasher::default();
+    hasher.write_u64(seed);
+    candidate.id().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn no_tie_break<Candidate>(_: &Candidate) -> u64 {
+    0
+}
+
+/// A 64-bit [FNV-1a](https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function)
+/// hasher, with a final mix so that all bits of the output depend on the input. Unlike
+/// `DefaultHasher`, the output is fixed: integers are always written as little-endian bytes, and
+/// `usize` & `isize` as 64 bits.
+struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        // The finalizer of SplitMix64.
+        let mut x = self.0;
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64)
+    }
+    fn write_i16(&mut self, i: i16) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_i32(&mut self, i: i32) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_i128(&mut self, i: i128) {
+        self.write(&i.to_le_bytes())
+    }
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64)
+    }
+}
+
+/// Whether `candidate` may be added to `selected`. It must not already be selected, and the combined
+/// fee of the selection must stay within the budget.
+fn is_eligible<Candidate>(
+    selected: &[&Candidate],
+    candidate: &Candidate,
+    options: &SelectionOptions<Candidate::Id>,
+) -> bool
+where
+    Candidate: crate::Candidate,
+{
+    selected.iter().all(|s| s.id() != candidate.id())
+        && within_budget(
+            selected.iter().copied().chain([candidate]),
+            options.fee_budget,
+        )
+}
+
+/// Whether the combined fee of `selected` is within the budget. See
+/// [`SelectionOptions::fee_budget`].
+fn within_budget<'c, Candidate>(
+    mut selected: impl Iterator<Item = &'c Candidate>,
+    fee_budget: Option<u128>,
+) -> bool
+where
+    Candidate: crate::Candidate + 'c,
+{
+    match fee_budget {
+        Some(budget) => selected
+            .try_fold(0_u128, |sum, c| sum.checked_add(c.fee_units()?))
+            .is_some_and(|fee| fee <= budget),
+        None => selected.map(|c| c.fee().as_f64()).sum::<f64>() <= 1.0,
+    }
+}
+
+/// Whether adding `candidate` to `selected` keeps the number of selected candidates in its group
+/// within `options.max_per_group`.
+fn within_group_cap<Candidate>(
+    selected: &[&Candidate],
+    candidate: &Candidate,
+    options: &SelectionOptions<Candidate::Id>,
+) -> bool
+where
+    Candidate: crate::Candidate,
+{
+    let (Some(max), Some(group)) = (options.max_per_group, candidate.group()) else {
+        return true;
+    };
+    selected.iter().filter(|s| s.group() == Some(group)).count() < max
+}
+
+/// The increase in the combined score of `selected` from adding `candidate`.
+fn marginal_score<'c, Candidate, S>(
+    current_score: Normalized,
+    selected: &mut S,
+    candidate: &'c Candidate,
+) -> NotNan<f64>
+where
+    Candidate: crate::Candidate,
+    S: Selection<'c, Candidate>,
+{
+    selected.push(candidate);
+    let potential_score = selected.score();
+    selected.pop();
+    NotNan::new(potential_score.as_f64() - current_score.as_f64()).unwrap()
+}
+
+/// The marginal score of `candidate` relative to its fee. See [`SelectionOptions`]. The divisor is
+/// kept above zero, since it may underflow for high price sensitivities.
+fn fee_adjusted<Candidate>(
+    marginal_score: NotNan<f64>,
+    candidate: &Candidate,
+    options: &SelectionOptions<Candidate::Id>,
+) -> NotNan<f64>
+where
+    Candidate: crate::Candidate,
+{
+    let fee = candidate.fee().as_f64().max(options.fee_floor);
+    let divisor = fee.powf(options.price_sensitivity).max(f64::MIN_POSITIVE);
+    marginal_score / NotNan::new(divisor).unwrap()
+}
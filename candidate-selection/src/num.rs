@@ -42,7 +42,7 @@ impl std::ops::Mul for Normalized {
 
 impl std::cmp::PartialOrd for Normalized {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.0.cmp(&other.0))
+        Some(self.cmp(other))
     }
 }
 
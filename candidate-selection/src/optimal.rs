@@ -0,0 +1,166 @@
+use std::time::{Duration, Instant};
+
+use arrayvec::ArrayVec;
+
+use crate::{greedy, no_tie_break, within_budget, Normalized, Selection, SelectionOptions};
+
+/// Caps on the work done by [`select_optimal`].
+#[derive(Clone, Copy, Debug)]
+pub struct SearchLimits {
+    /// Maximum number of candidate sets to evaluate.
+    pub max_nodes: usize,
+    /// Maximum wall-clock time to spend searching.
+    pub max_duration: Option<Duration>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self {
+            max_nodes: 100_000,
+            max_duration: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OptimalSelection<'c, Candidate, const LIMIT: usize> {
+    /// The best set of candidates found.
+    pub selected: ArrayVec<&'c Candidate, LIMIT>,
+    /// The combined score of `selected`.
+    pub score: Normalized,
+    /// True if the search space was exhausted, meaning that `selected` is optimal. Otherwise, the
+    /// search was stopped by the `SearchLimits`.
+    pub complete: bool,
+    /// Number of candidate sets evaluated.
+    pub nodes: usize,
+}
+
+/// Select the set of up to `LIMIT` candidates with the highest combined score, using a
+/// branch-and-bound search.
+///
+/// Sets are only considered if their summed fee is within the budget: a fee sum of at most 1, or at
+/// most `fee_budget` when set (see [`SelectionOptions::fee_budget`]).
+/// Among sets with equal scores, the one with the lowest summed fee is preferred. The search is
+/// seeded with the result of [`select`](crate::select) within the same budget, so the result is
+/// never worse than the greedy selection, even when the search is stopped early.
+///
+/// Candidates are explored in order of descending individual score. A branch is pruned when adding
+/// a candidate would exceed the fee budget, or when `Candidate::score_bound` shows that no superset
+/// of the branch can beat the best set found so far.
+pub fn select_optimal<Candidate, const LIMIT: usize>(
+    candidates: &[Candidate],
+    fee_budget: Option<u128>,
+    limits: SearchLimits,
+) -> OptimalSelection<'_, Candidate, LIMIT>
+where
+    Candidate: crate::Candidate,
+{
+    assert!(LIMIT > 0);
+
+    let mut order: Vec<&Candidate> = candidates.iter().collect();
+    order.sort_by_cached_key(|c| std::cmp::Reverse(c.score()));
+
+    let options = SelectionOptions {
+        fee_budget,
+        ..SelectionOptions::DEFAULT
+    };
+    let (greedy, _): (ArrayVec<&Candidate, LIMIT>, _) = greedy(
+        candidates,
+        LIMIT,
+        ArrayVec::new(),
+        &options,
+        no_tie_break,
+        None,
+    );
+    let greedy_fee = fee(&greedy);
+    let mut search = Search {
+        order,
+        deadline: limits.max_duration.map(|d| Instant::now() + d),
+        max_nodes: limits.max_nodes,
+        nodes: 0,
+        complete: true,
+        best: ArrayVec::new(),
+        best_score: Normalized::ZERO,
+        best_fee: 0.0,
+        fee_budget,
+    };
+    if within_budget(greedy.iter().copied(), fee_budget) {
+        search.best_score = greedy.score();
+        search.best_fee = greedy_fee;
+        search.best = greedy;
+    }
+    search.branch(0, &mut ArrayVec::new(), 0.0);
+
+    OptimalSelection {
+        selected: search.best,
+        score: search.best_score,
+        complete: search.complete,
+        nodes: search.nodes,
+    }
+}
+
+struct Search<'c, Candidate, const LIMIT: usize> {
+    order: Vec<&'c Candidate>,
+    deadline: Option<Instant>,
+    max_nodes: usize,
+    nodes: usize,
+    complete: bool,
+    best: ArrayVec<&'c Candidate, LIMIT>,
+    best_score: Normalized,
+    best_fee: f64,
+    fee_budget: Option<u128>,
+}
+
+impl<'c, Candidate, const LIMIT: usize> Search<'c, Candidate, LIMIT>
+where
+    Candidate: crate::Candidate,
+{
+    fn branch(&mut self, start: usize, selected: &mut ArrayVec<&'c Candidate, LIMIT>, fee: f64) {
+        for index in start..self.order.len() {
+            if self.exhausted() {
+                self.complete = false;
+                return;
+            }
+            let candidate = self.order[index];
+            if selected.iter().any(|s| s.id() == candidate.id()) {
+                continue;
+            }
+            let fee = fee + candidate.fee().as_f64();
+            selected.push(candidate);
+            if !within_budget(selected.iter().copied(), self.fee_budget) {
+                selected.pop();
+                continue;
+            }
+            if Candidate::score_bound::<LIMIT>(selected) >= self.best_score {
+                self.nodes += 1;
+                let score = selected.score();
+                if (score > self.best_score)
+                    || ((score == self.best_score) && (fee < self.best_fee))
+                {
+                    self.best = selected.clone();
+                    self.best_score = score;
+                    self.best_fee = fee;
+                }
+                if !selected.is_full() {
+                    self.branch(index + 1, selected, fee);
+                }
+            }
+            selected.pop();
+        }
+    }
+
+    fn exhausted(&self) -> bool {
+        if self.nodes >= self.max_nodes {
+            return true;
+        }
+        // Checking the clock is relatively expensive, so only do it periodically.
+        self.nodes.is_multiple_of(64) && self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+}
+
+fn fee<Candidate>(selected: &[&Candidate]) -> f64
+where
+    Candidate: crate::Candidate,
+{
+    selected.iter().map(|c| c.fee().as_f64()).sum()
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeSet;
+
+use arrayvec::ArrayVec;
+
+use crate::StopReason;
+
+/// Options for how [`select_with_options`](crate::select_with_options) selects candidates, including
+/// how it trades off the scores of candidates against their fees.
+///
+/// Each round of the selection picks the candidate with the highest fee-adjusted marginal score:
+/// `marginal_score / max(fee, fee_floor) ^ price_sensitivity`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectionOptions<Id> {
+    /// How strongly fees count against candidates. At 0, fees are ignored (other than the budget)
+    /// and candidates are ranked by marginal score alone. At 1, candidates are ranked by marginal
+    /// score per unit of fee. Above 1, cheaper candidates are preferred even when they add
+    /// proportionally less to the score. Must be finite and non-negative.
+    pub price_sensitivity: f64,
+    /// The fee, as a fraction of the budget, below which candidates are not considered any
+    /// cheaper. Lowering the floor increases the advantage of very cheap & free candidates. Must be
+    /// in (0, 1].
+    pub fee_floor: f64,
+    /// When set, each round samples the selected candidate instead of always picking the one with
+    /// the highest fee-adjusted marginal score. This spreads selections over more candidates, so
+    /// that their performance can be measured.
+    pub softmax: Option<Softmax>,
+    /// When set, ties between candidates with the same fee-adjusted marginal score are broken by a
+    /// hash of this seed & the candidate IDs, instead of selecting the last of them. Using a
+    /// different seed per query spreads selections evenly over tied candidates, regardless of
+    /// the order they are provided in.
+    pub tie_break_seed: Option<u64>,
+    /// Candidates that are selected before all others, regardless of their scores. They are placed
+    /// first in the selection, in the order they are provided, and count toward the limit, the fee
+    /// budget, and the group cap. Pinned candidates that would exceed any of these are not
+    /// selected.
+    pub pinned: BTreeSet<Id>,
+    /// Candidates that are never selected, even if they are also pinned.
+    pub excluded: BTreeSet<Id>,
+    /// When set, at most this many candidates are selected from each group given by
+    /// [`Candidate::group`](crate::Candidate::group). This avoids correlated failures from
+    /// selecting candidates with the same operator, region, etc. Candidates without a group are
+    /// not limited.
+    pub max_per_group: Option<usize>,
+    /// While fewer than this many candidates are selected and no candidate has a positive marginal
+    /// score, the candidate with the highest fee-adjusted individual score is selected instead,
+    /// skipping candidates with an individual score of zero. Use
+    /// [`try_select_with_options`](crate::try_select_with_options) to get an error when the
+    /// minimum can't be met within the limit & fee budget.
+    pub min_selected: usize,
+    /// When set, the combined fee of the selected candidates is checked against this budget, in the
+    /// integer units of [`Candidate::fee_units`](crate::Candidate::fee_units), instead of by
+    /// summing their `fee` fractions. The fractions may round to more than 1 for fees that add up
+    /// to exactly the budget. Candidates without `fee_units` are never selected.
+    pub fee_budget: Option<u128>,
+}
+
+/// Sample each selected candidate from the softmax over the fee-adjusted marginal scores of the
+/// candidates with a positive marginal score. Candidates that would not increase the combined
+/// score of the selection are never sampled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Softmax {
+    /// Lower temperatures concentrate selections on the highest scoring candidates, and higher
+    /// temperatures spread them more evenly. Must be greater than 0.
+    pub temperature: f64,
+    /// The same seed always results in the same selections for the same candidates.
+    pub seed: u64,
+}
+
+impl<Id> SelectionOptions<Id> {
+    pub const DEFAULT: Self = Self {
+        price_sensitivity: 1.0,
+        fee_floor: 0.01,
+        softmax: None,
+        tie_break_seed: None,
+        pinned: BTreeSet::new(),
+        excluded: BTreeSet::new(),
+        max_per_group: None,
+        min_selected: 0,
+        fee_budget: None,
+    };
+}
+
+impl<Id> SelectionOptions<Id> {
+    pub fn validate(&self) -> Result<(), InvalidSelectionOptions> {
+        if !self.price_sensitivity.is_finite() || (self.price_sensitivity < 0.0) {
+            return Err(InvalidSelectionOptions::PriceSensitivity);
+        }
+        if !((self.fee_floor > 0.0) && (self.fee_floor <= 1.0)) {
+            return Err(InvalidSelectionOptions::FeeFloor);
+        }
+        if let Some(softmax) = &self.softmax {
+            if !softmax.temperature.is_finite() || (softmax.temperature <= 0.0) {
+                return Err(InvalidSelectionOptions::SoftmaxTemperature);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<Id> Default for SelectionOptions<Id> {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidSelectionOptions {
+    PriceSensitivity,
+    FeeFloor,
+    SoftmaxTemperature,
+}
+
+impl std::fmt::Display for InvalidSelectionOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PriceSensitivity => {
+                write!(f, "price_sensitivity must be finite and non-negative")
+            }
+            Self::FeeFloor => write!(f, "fee_floor must be in (0, 1]"),
+            Self::SoftmaxTemperature => {
+                write!(f, "softmax temperature must be finite and greater than 0")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidSelectionOptions {}
+
+/// The error returned by [`try_select_with_options`](crate::try_select_with_options).
+#[derive(Debug)]
+pub enum TrySelectError<'c, Candidate, const LIMIT: usize> {
+    InvalidOptions(InvalidSelectionOptions),
+    BelowMinimum(BelowMinimum<'c, Candidate, LIMIT>),
+}
+
+impl<Candidate, const LIMIT: usize> From<InvalidSelectionOptions>
+    for TrySelectError<'_, Candidate, LIMIT>
+{
+    fn from(err: InvalidSelectionOptions) -> Self {
+        Self::InvalidOptions(err)
+    }
+}
+
+impl<Candidate, const LIMIT: usize> std::fmt::Display for TrySelectError<'_, Candidate, LIMIT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidOptions(err) => err.fmt(f),
+            Self::BelowMinimum(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<Candidate, const LIMIT: usize> std::error::Error for TrySelectError<'_, Candidate, LIMIT> where
+    Candidate: std::fmt::Debug
+{
+}
+
+/// The error returned by [`try_select_with_options`](crate::try_select_with_options) when fewer
+/// than `min_selected` candidates could be selected.
+#[derive(Debug)]
+pub struct BelowMinimum<'c, Candidate, const LIMIT: usize> {
+    /// The candidates that could be selected.
+    pub selected: ArrayVec<&'c Candidate, LIMIT>,
+    pub min_selected: usize,
+    /// Why the selection stopped before reaching the minimum.
+    pub stop: StopReason,
+}
+
+impl<Candidate, const LIMIT: usize> std::fmt::Display for BelowMinimum<'_, Candidate, LIMIT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.stop {
+            StopReason::LimitReached => "limit reached",
+            StopReason::NoCandidatesRemaining => "no candidates remaining within the fee budget",
+            StopReason::NoPositiveMarginalScore => {
+                "no candidates remaining with a score above zero"
+            }
+        };
+        write!(
+            f,
+            "selected {} of at least {} candidates ({reason})",
+            self.selected.len(),
+            self.min_selected,
+        )
+    }
+}
+
+impl<Candidate, const LIMIT: usize> std::error::Error for BelowMinimum<'_, Candidate, LIMIT> where
+    Candidate: std::fmt::Debug
+{
+}
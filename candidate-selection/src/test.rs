@@ -1,6 +1,23 @@
+use std::cell::Cell;
+
 use proptest::{prelude::prop, prop_assert_eq, prop_compose, proptest};
 
-use crate::{select, ArrayVec, Candidate, Normalized};
+use crate::{
+    select, select_dyn, select_explained, select_lazy, select_optimal, select_with_options,
+    try_select_with_options, ArrayVec, Candidate, InvalidSelectionOptions, Normalized,
+    SearchLimits, SelectionOptions, Softmax, StopReason, TrySelectError,
+};
+
+thread_local! {
+    /// Number of calls to `score` or `score_many` made on the current thread.
+    static SCORE_CALLS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn score_calls<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    SCORE_CALLS.set(0);
+    let result = f();
+    (result, SCORE_CALLS.get())
+}
 
 #[derive(Debug)]
 struct TestCandidate {
@@ -9,18 +26,51 @@ struct TestCandidate {
     score: Normalized,
 }
 
+/// A candidate in the group given by `id / 10`.
+#[derive(Debug)]
+struct GroupedCandidate(TestCandidate);
+
+impl Candidate for GroupedCandidate {
+    type Id = u8;
+    fn id(&self) -> &Self::Id {
+        &self.0.id
+    }
+    fn group(&self) -> Option<u64> {
+        Some(self.0.id as u64 / 10)
+    }
+    fn fee(&self) -> Normalized {
+        self.0.fee
+    }
+    fn score(&self) -> Normalized {
+        self.0.score
+    }
+    fn score_many<const LIMIT: usize>(candidates: &[&Self]) -> Normalized {
+        Self::score_many_dyn(candidates)
+    }
+    fn score_many_dyn(candidates: &[&Self]) -> Normalized {
+        let candidates: Vec<&TestCandidate> = candidates.iter().map(|c| &c.0).collect();
+        TestCandidate::score_many_dyn(&candidates)
+    }
+}
+
 impl Candidate for TestCandidate {
     type Id = u8;
-    fn id(&self) -> Self::Id {
-        self.id
+    const SUBMODULAR: bool = true;
+    fn id(&self) -> &Self::Id {
+        &self.id
     }
     fn fee(&self) -> Normalized {
         self.fee
     }
     fn score(&self) -> Normalized {
+        SCORE_CALLS.set(SCORE_CALLS.get() + 1);
         self.score
     }
     fn score_many<const LIMIT: usize>(candidates: &[&Self]) -> Normalized {
+        Self::score_many_dyn(candidates)
+    }
+    fn score_many_dyn(candidates: &[&Self]) -> Normalized {
+        SCORE_CALLS.set(SCORE_CALLS.get() + 1);
         let mut combined_score = 0.0;
         for candidate in candidates {
             combined_score = (combined_score + candidate.score.as_f64()).min(1.0);
@@ -54,4 +104,576 @@ proptest! {
         prop_assert_eq!(true, selections.iter().all(|s| s.score > Normalized::ZERO));
         prop_assert_eq!(exists_acceptable_candidate, !selections.is_empty());
     }
+
+    #[test]
+    fn acceptable_candidates_sampled(
+        candidates in prop::collection::vec(candidate(), 1..16),
+        temperature in 0.01..100.0,
+        seed: u64,
+    ) {
+        let exists_acceptable_candidate = candidates.iter().any(|c| c.score > Normalized::ZERO);
+        let options = SelectionOptions {
+            softmax: Some(Softmax { temperature, seed }),
+            ..Default::default()
+        };
+
+        let selections: ArrayVec<&TestCandidate, 1> = select_with_options(&candidates, &options).unwrap();
+        prop_assert_eq!(exists_acceptable_candidate, !selections.is_empty());
+        prop_assert_eq!(true, selections.iter().all(|s| s.score > Normalized::ZERO));
+
+        let selections: ArrayVec<&TestCandidate, 3> = select_with_options(&candidates, &options).unwrap();
+        prop_assert_eq!(true, selections.iter().all(|s| s.score > Normalized::ZERO));
+        prop_assert_eq!(exists_acceptable_candidate, !selections.is_empty());
+    }
+
+    #[test]
+    fn selection_within_budget(
+        candidates in prop::collection::vec(candidate(), 1..16),
+    ) {
+        let selections: ArrayVec<&TestCandidate, 5> = select(&candidates);
+        prop_assert_eq!(true, selections.iter().map(|s| s.fee.as_f64()).sum::<f64>() <= 1.0);
+        let selections: ArrayVec<&TestCandidate, 5> = select_lazy(&candidates);
+        prop_assert_eq!(true, selections.iter().map(|s| s.fee.as_f64()).sum::<f64>() <= 1.0);
+    }
+}
+
+#[test]
+fn selection_options() {
+    let candidates = [
+        TestCandidate {
+            id: 0,
+            fee: Normalized::new(0.5).unwrap(),
+            score: Normalized::new(0.9).unwrap(),
+        },
+        TestCandidate {
+            id: 1,
+            fee: Normalized::new(0.1).unwrap(),
+            score: Normalized::new(0.3).unwrap(),
+        },
+        TestCandidate {
+            id: 2,
+            fee: Normalized::ZERO,
+            score: Normalized::new(0.01).unwrap(),
+        },
+    ];
+    let select = |price_sensitivity, fee_floor| {
+        let options = SelectionOptions {
+            price_sensitivity,
+            fee_floor,
+            ..Default::default()
+        };
+        ids(&select_with_options::<_, 1>(&candidates, &options).unwrap())
+    };
+    assert_eq!(vec![1], ids(&crate::select::<_, 1>(&candidates)));
+    assert_eq!(vec![1], select(1.0, 0.01));
+    // quality regardless of price
+    assert_eq!(vec![0], select(0.0, 0.01));
+    // a lower fee floor favors the free candidate
+    assert_eq!(vec![2], select(1.0, 0.0001));
+    // strong price sensitivity favors cheaper candidates
+    assert_eq!(vec![2], select(3.0, 0.01));
+    // The divisors underflow, and must not result in 0 / 0 for the candidate with a zero score.
+    let zero = [
+        TestCandidate {
+            id: 0,
+            fee: Normalized::new(0.1).unwrap(),
+            score: Normalized::new(0.5).unwrap(),
+        },
+        TestCandidate {
+            id: 1,
+            fee: Normalized::new(0.1).unwrap(),
+            score: Normalized::ZERO,
+        },
+    ];
+    let options = SelectionOptions {
+        price_sensitivity: 200.0,
+        ..Default::default()
+    };
+    let selected = select_with_options::<_, 2>(&zero, &options).unwrap();
+    assert_eq!(vec![0], ids(&selected));
+
+    let invalid = SelectionOptions {
+        fee_floor: 0.0,
+        ..Default::default()
+    };
+    assert_eq!(
+        Some(InvalidSelectionOptions::FeeFloor),
+        select_with_options::<_, 1>(&candidates, &invalid).err()
+    );
+}
+
+#[test]
+fn softmax_selection() {
+    let candidates: Vec<TestCandidate> = (0..4)
+        .map(|id| TestCandidate {
+            id,
+            fee: Normalized::new(0.1).unwrap(),
+            score: Normalized::new(0.1 * (id + 1) as f64).unwrap(),
+        })
+        .collect();
+    let counts = |temperature| {
+        let mut counts = [0; 4];
+        for seed in 0..1_000 {
+            let options = SelectionOptions {
+                softmax: Some(Softmax { temperature, seed }),
+                ..Default::default()
+            };
+            let selected = select_with_options::<_, 1>(&candidates, &options).unwrap();
+            counts[selected[0].id as usize] += 1;
+        }
+        counts
+    };
+
+    let options = SelectionOptions {
+        softmax: Some(Softmax {
+            temperature: 1.0,
+            seed: 7,
+        }),
+        ..Default::default()
+    };
+    let selected = select_with_options::<_, 3>(&candidates, &options).unwrap();
+    assert_eq!(
+        ids(&selected),
+        ids(&select_with_options::<_, 3>(&candidates, &options).unwrap())
+    );
+
+    // fee-adjusted marginal scores are 1, 2, 3, 4
+    let cold = counts(0.01);
+    assert_eq!([0, 0, 0, 1_000], cold);
+    let warm = counts(1.0);
+    assert!(warm.windows(2).all(|w| w[0] < w[1]));
+    assert!(warm[3] < 800);
+    let hot = counts(1_000.0);
+    assert!(hot.iter().all(|&n| (200..300).contains(&n)));
+}
+
+#[test]
+fn tie_breaking() {
+    let candidates: Vec<TestCandidate> = (0..4)
+        .map(|id| TestCandidate {
+            id,
+            fee: Normalized::new(0.1).unwrap(),
+            score: Normalized::new(0.5).unwrap(),
+        })
+        .collect();
+    let reversed: Vec<TestCandidate> = (0..4)
+        .rev()
+        .map(|id| TestCandidate {
+            id,
+            fee: Normalized::new(0.1).unwrap(),
+            score: Normalized::new(0.5).unwrap(),
+        })
+        .collect();
+    let mut counts = [0; 4];
+    for seed in 0..4_000 {
+        let options = SelectionOptions {
+            tie_break_seed: Some(seed),
+            ..Default::default()
+        };
+        let selected = select_with_options::<_, 1>(&candidates, &options).unwrap();
+        let reversed = select_with_options::<_, 1>(&reversed, &options).unwrap();
+        assert_eq!(selected[0].id, reversed[0].id);
+        counts[selected[0].id as usize] += 1;
+    }
+    assert!(counts.iter().all(|&n| (900..1_100).contains(&n)));
+
+    // The ranks must not change across Rust releases or platforms.
+    let picks: Vec<u8> = (0..8)
+        .map(|seed| {
+            let options = SelectionOptions {
+                tie_break_seed: Some(seed),
+                ..Default::default()
+            };
+            select_with_options::<_, 1>(&candidates, &options).unwrap()[0].id
+        })
+        .collect();
+    assert_eq!(picks, vec![0, 2, 0, 0, 3, 1, 2, 3]);
+}
+
+#[test]
+fn pinned_and_excluded() {
+    let candidates: Vec<TestCandidate> = [(0.3, 0.5), (0.2, 0.4), (0.1, 0.3), (0.05, 0.0)]
+        .into_iter()
+        .enumerate()
+        .map(|(id, (fee, score))| TestCandidate {
+            id: id as u8,
+            fee: Normalized::new(fee).unwrap(),
+            score: Normalized::new(score).unwrap(),
+        })
+        .collect();
+    let select = |pinned: &[u8], excluded: &[u8]| {
+        let options = SelectionOptions {
+            pinned: pinned.iter().copied().collect(),
+            excluded: excluded.iter().copied().collect(),
+            ..Default::default()
+        };
+        ids(&select_with_options::<_, 2>(&candidates, &options).unwrap())
+    };
+    assert_eq!(vec![2, 1], select(&[], &[]));
+    assert_eq!(vec![2, 0], select(&[], &[1]));
+    // pinned candidates are selected regardless of their score
+    assert_eq!(vec![3, 2], select(&[3], &[]));
+    assert_eq!(vec![0, 3], select(&[3, 0], &[]));
+    assert_eq!(vec![0, 2], select(&[3, 0], &[3]));
+    assert_eq!(vec![0, 1], select(&[0, 1, 2], &[]));
+
+    // pinned candidates count toward the fee budget
+    let options = SelectionOptions {
+        pinned: [0].into(),
+        ..Default::default()
+    };
+    let candidates = [
+        TestCandidate {
+            id: 0,
+            fee: Normalized::new(0.8).unwrap(),
+            score: Normalized::new(0.1).unwrap(),
+        },
+        TestCandidate {
+            id: 1,
+            fee: Normalized::new(0.3).unwrap(),
+            score: Normalized::new(0.9).unwrap(),
+        },
+    ];
+    assert_eq!(
+        vec![0],
+        ids(&select_with_options::<_, 2>(&candidates, &options).unwrap())
+    );
+}
+
+proptest! {
+    #[test]
+    fn group_cap(
+        candidates in prop::collection::vec(candidate(), 1..32),
+        max_per_group in 1..3_usize,
+    ) {
+        let candidates: Vec<GroupedCandidate> = candidates.into_iter().map(GroupedCandidate).collect();
+        let options = SelectionOptions {
+            max_per_group: Some(max_per_group),
+            ..Default::default()
+        };
+        let selected: ArrayVec<&GroupedCandidate, 5> = select_with_options(&candidates, &options).unwrap();
+        for group in 0..26 {
+            let count = selected.iter().filter(|c| c.group() == Some(group)).count();
+            prop_assert_eq!(true, count <= max_per_group);
+        }
+        let exists_acceptable_candidate = candidates.iter().any(|c| c.0.score > Normalized::ZERO);
+        prop_assert_eq!(exists_acceptable_candidate, !selected.is_empty());
+    }
+}
+
+#[test]
+fn min_selected() {
+    let candidates = [
+        TestCandidate {
+            id: 0,
+            fee: Normalized::new(0.3).unwrap(),
+            score: Normalized::ONE,
+        },
+        TestCandidate {
+            id: 1,
+            fee: Normalized::new(0.5).unwrap(),
+            score: Normalized::new(0.5).unwrap(),
+        },
+        TestCandidate {
+            id: 2,
+            fee: Normalized::new(0.2).unwrap(),
+            score: Normalized::new(0.1).unwrap(),
+        },
+        TestCandidate {
+            id: 3,
+            fee: Normalized::ZERO,
+            score: Normalized::ZERO,
+        },
+    ];
+    let options = |min_selected| SelectionOptions {
+        min_selected,
+        ..Default::default()
+    };
+    assert_eq!(vec![0], ids(&select::<_, 3>(&candidates)));
+    // Further candidates add nothing to the combined score, which is already 1. So they are selected
+    // by their fee-adjusted individual scores: 0.5 / 0.5 for candidate 1, and 0.1 / 0.2 for 2.
+    let selected = try_select_with_options::<_, 3>(&candidates, &options(2)).unwrap();
+    assert_eq!(vec![0, 1], ids(&selected));
+    let selected = try_select_with_options::<_, 3>(&candidates, &options(3)).unwrap();
+    assert_eq!(vec![0, 1, 2], ids(&selected));
+
+    let Err(TrySelectError::BelowMinimum(err)) =
+        try_select_with_options::<_, 3>(&candidates[1..], &options(3))
+    else {
+        panic!("expected BelowMinimum");
+    };
+    assert_eq!(vec![1, 2], ids(&err.selected));
+    assert_eq!(StopReason::NoPositiveMarginalScore, err.stop);
+    assert_eq!(
+        "selected 2 of at least 3 candidates (no candidates remaining with a score above zero)",
+        err.to_string()
+    );
+    let candidates = [
+        TestCandidate {
+            id: 0,
+            fee: Normalized::new(0.6).unwrap(),
+            score: Normalized::new(0.5).unwrap(),
+        },
+        TestCandidate {
+            id: 1,
+            fee: Normalized::new(0.6).unwrap(),
+            score: Normalized::new(0.5).unwrap(),
+        },
+    ];
+    let Err(TrySelectError::BelowMinimum(err)) =
+        try_select_with_options::<_, 3>(&candidates, &options(2))
+    else {
+        panic!("expected BelowMinimum");
+    };
+    assert_eq!(StopReason::NoCandidatesRemaining, err.stop);
+}
+
+/// A candidate where some pairs of candidates are worth more together than apart.
+#[derive(Debug)]
+struct SynergyCandidate {
+    id: u8,
+    score: Normalized,
+}
+
+impl Candidate for SynergyCandidate {
+    type Id = u8;
+    fn id(&self) -> &Self::Id {
+        &self.id
+    }
+    fn fee(&self) -> Normalized {
+        Normalized::new(0.1).unwrap()
+    }
+    fn score(&self) -> Normalized {
+        self.score
+    }
+    fn score_many<const LIMIT: usize>(candidates: &[&Self]) -> Normalized {
+        Self::score_many_dyn(candidates)
+    }
+    fn score_many_dyn(candidates: &[&Self]) -> Normalized {
+        let sum = candidates.iter().map(|c| c.score.as_f64()).sum::<f64>();
+        let pair = candidates.iter().filter(|c| c.id < 2).count() == 2;
+        Normalized::new((sum + if pair { 0.5 } else { 0.0 }).min(1.0)).unwrap()
+    }
+}
+
+/// A candidate that only implements `score_many` for sets with a size known at compile time.
+#[derive(Debug)]
+struct ConstLimitCandidate(TestCandidate);
+
+impl Candidate for ConstLimitCandidate {
+    type Id = u8;
+    fn id(&self) -> &Self::Id {
+        &self.0.id
+    }
+    fn fee(&self) -> Normalized {
+        self.0.fee
+    }
+    fn score(&self) -> Normalized {
+        self.0.score
+    }
+    fn score_many<const LIMIT: usize>(candidates: &[&Self]) -> Normalized {
+        let candidates: ArrayVec<&TestCandidate, LIMIT> = candidates.iter().map(|c| &c.0).collect();
+        TestCandidate::score_many_dyn(&candidates)
+    }
+}
+
+#[test]
+fn default_score_many_dyn() {
+    let candidates: Vec<ConstLimitCandidate> = (0..20)
+        .map(|id| {
+            ConstLimitCandidate(TestCandidate {
+                id,
+                fee: Normalized::ZERO,
+                score: Normalized::new(0.01).unwrap(),
+            })
+        })
+        .collect();
+    assert_eq!(5, select_dyn(&candidates, 5, None).len());
+    assert_eq!(20, select_dyn(&candidates, 20, None).len());
+}
+
+proptest! {
+    #[test]
+    fn runtime_limit_selection_matches_const(
+        candidates in prop::collection::vec(candidate(), 1..16),
+    ) {
+        let expected: ArrayVec<&TestCandidate, 1> = select(&candidates);
+        prop_assert_eq!(ids(&expected), ids(&select_dyn(&candidates, 1, None)));
+
+        let expected: ArrayVec<&TestCandidate, 3> = select(&candidates);
+        prop_assert_eq!(ids(&expected), ids(&select_dyn(&candidates, 3, None)));
+    }
+}
+
+proptest! {
+    #[test]
+    fn lazy_selection_matches_exact(
+        candidates in prop::collection::vec(candidate(), 1..32),
+    ) {
+        let exact: ArrayVec<&TestCandidate, 1> = select(&candidates);
+        let lazy: ArrayVec<&TestCandidate, 1> = select_lazy(&candidates);
+        prop_assert_eq!(ids(&exact), ids(&lazy));
+
+        let exact: ArrayVec<&TestCandidate, 3> = select(&candidates);
+        let lazy: ArrayVec<&TestCandidate, 3> = select_lazy(&candidates);
+        prop_assert_eq!(ids(&exact), ids(&lazy));
+    }
+}
+
+fn ids<C>(selections: &[&C]) -> Vec<C::Id>
+where
+    C: Candidate,
+    C::Id: Clone,
+{
+    selections.iter().map(|c| c.id().clone()).collect()
+}
+
+#[test]
+fn lazy_selection_score_calls() {
+    let candidates: Vec<TestCandidate> = (0..=u8::MAX)
+        .map(|i| TestCandidate {
+            id: i,
+            fee: Normalized::new((i % 7) as f64 / 10.0).unwrap(),
+            score: Normalized::new((i % 13) as f64 / 100.0).unwrap(),
+        })
+        .collect();
+
+    let (exact, exact_calls) = score_calls(|| select::<_, 3>(&candidates));
+    let (lazy, lazy_calls) = score_calls(|| select_lazy::<_, 3>(&candidates));
+    assert_eq!(ids(&exact), ids(&lazy));
+    assert!(exact_calls > 700);
+    assert!(lazy_calls < 300);
+}
+
+#[test]
+fn lazy_selection_falls_back_when_not_submodular() {
+    let candidates = [
+        SynergyCandidate {
+            id: 0,
+            score: Normalized::new(0.1).unwrap(),
+        },
+        SynergyCandidate {
+            id: 1,
+            score: Normalized::new(0.2).unwrap(),
+        },
+        SynergyCandidate {
+            id: 2,
+            score: Normalized::new(0.25).unwrap(),
+        },
+        SynergyCandidate {
+            id: 3,
+            score: Normalized::new(0.15).unwrap(),
+        },
+    ];
+    // Lazy evaluation would never re-evaluate candidate 0 in the last round, and select 3 instead.
+    let exact: ArrayVec<&SynergyCandidate, 3> = select(&candidates);
+    let lazy: ArrayVec<&SynergyCandidate, 3> = select_lazy(&candidates);
+    assert_eq!(vec![2, 1, 0], ids(&exact));
+    assert_eq!(ids(&exact), ids(&lazy));
+}
+
+proptest! {
+    #[test]
+    fn optimal_selection(
+        candidates in prop::collection::vec(candidate(), 1..10),
+    ) {
+        let greedy: ArrayVec<&TestCandidate, 3> = select(&candidates);
+        let optimal = select_optimal::<_, 3>(&candidates, None, SearchLimits::default());
+        prop_assert_eq!(true, optimal.complete);
+        prop_assert_eq!(true, optimal.selected.iter().map(|c| c.fee.as_f64()).sum::<f64>() <= 1.0);
+
+        // brute force over all sets of up to 3 candidates within budget
+        let mut best = Normalized::ZERO;
+        for (i, a) in candidates.iter().enumerate() {
+            for (j, b) in candidates.iter().enumerate().skip(i) {
+                for c in candidates.iter().skip(j) {
+                    let mut set: Vec<&TestCandidate> = vec![a, b, c];
+                    set.sort_by_key(|c| c.id);
+                    set.dedup_by_key(|c| c.id);
+                    if set.iter().map(|c| c.fee.as_f64()).sum::<f64>() <= 1.0 {
+                        best = best.max(TestCandidate::score_many::<3>(&set));
+                    }
+                }
+            }
+        }
+        prop_assert_eq!(best, optimal.score);
+
+        if greedy.iter().map(|c| c.fee.as_f64()).sum::<f64>() <= 1.0 {
+            prop_assert_eq!(true, TestCandidate::score_many::<3>(&greedy) <= optimal.score);
+        }
+    }
+}
+
+#[test]
+fn optimal_selection_node_limit() {
+    let candidates: Vec<TestCandidate> = (0..64)
+        .map(|i| TestCandidate {
+            id: i,
+            fee: Normalized::ZERO,
+            score: Normalized::new(0.01).unwrap(),
+        })
+        .collect();
+    let limits = SearchLimits {
+        max_nodes: 100,
+        max_duration: None,
+    };
+    let optimal = select_optimal::<_, 3>(&candidates, None, limits);
+    assert!(!optimal.complete);
+    assert_eq!(100, optimal.nodes);
+    assert_eq!(3, optimal.selected.len());
+}
+
+proptest! {
+    #[test]
+    fn explained_selection_matches_select(
+        candidates in prop::collection::vec(candidate(), 1..16),
+    ) {
+        let expected: ArrayVec<&TestCandidate, 3> = select(&candidates);
+        let explained = select_explained::<_, 3>(&candidates, None);
+        prop_assert_eq!(ids(&expected), ids(&explained.selected));
+        let traced: Vec<u8> = explained.rounds.iter().filter_map(|r| r.selected).copied().collect();
+        prop_assert_eq!(ids(&expected), traced);
+        let extra_round = (explained.stop != StopReason::LimitReached) as usize;
+        prop_assert_eq!(expected.len() + extra_round, explained.rounds.len());
+    }
+}
+
+#[test]
+fn explained_selection() {
+    let candidates = [
+        TestCandidate {
+            id: 0,
+            fee: Normalized::new(0.5).unwrap(),
+            score: Normalized::new(0.5).unwrap(),
+        },
+        TestCandidate {
+            id: 1,
+            fee: Normalized::new(0.1).unwrap(),
+            score: Normalized::new(0.2).unwrap(),
+        },
+        TestCandidate {
+            id: 2,
+            fee: Normalized::ZERO,
+            score: Normalized::ZERO,
+        },
+    ];
+    let explained = select_explained::<_, 3>(&candidates, None);
+    assert_eq!(vec![1, 0], ids(&explained.selected));
+    assert_eq!(StopReason::NoPositiveMarginalScore, explained.stop);
+
+    let first_round = &explained.rounds[0];
+    assert_eq!(Some(&1), first_round.selected);
+    assert_eq!(3, first_round.evaluations.len());
+    assert_eq!(0.5, first_round.evaluations[0].marginal_score);
+    assert_eq!(1.0, first_round.evaluations[0].fee_adjusted_score);
+    assert_eq!(2.0, first_round.evaluations[1].fee_adjusted_score);
+
+    let last_round = explained.rounds.last().unwrap();
+    assert_eq!(None, last_round.selected);
+    assert_eq!(1, last_round.evaluations.len());
+
+    let explained = select_explained::<_, 1>(&candidates, None);
+    assert_eq!(StopReason::LimitReached, explained.stop);
+    let explained = select_explained::<_, 1>(&candidates[..0], None);
+    assert_eq!(StopReason::NoCandidatesRemaining, explained.stop);
 }
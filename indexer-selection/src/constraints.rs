@@ -0,0 +1,67 @@
+use crate::Candidate;
+
+/// Requirements that a candidate must meet to be considered for selection at all, regardless of
+/// how well it scores on other criteria. Unset constraints are not checked.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Constraints {
+    pub max_seconds_behind: Option<u32>,
+    pub min_slashable_grt: Option<u64>,
+    pub min_success_rate: Option<f64>,
+    pub max_latency_ms: Option<u16>,
+    pub max_fee: Option<u128>,
+}
+
+/// A constraint that rejected a candidate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Constraint {
+    MaxSecondsBehind,
+    MinSlashableGrt,
+    MinSuccessRate,
+    MaxLatency,
+    MaxFee,
+    /// The fee of the candidate alone exceeds the budget of the selection.
+    FeeBudget,
+    /// The candidate has the same ID as an earlier candidate.
+    DuplicateId,
+}
+
+#[derive(Debug)]
+pub struct Rejection<'c, I, D> {
+    pub candidate: &'c Candidate<I, D>,
+    /// The first constraint that the candidate failed to meet.
+    pub constraint: Constraint,
+}
+
+impl Constraints {
+    /// Returns the first constraint that the candidate fails to meet, if any.
+    pub fn check<I, D>(&self, candidate: &Candidate<I, D>) -> Result<(), Constraint> {
+        let Self {
+            max_seconds_behind,
+            min_slashable_grt,
+            min_success_rate,
+            max_latency_ms,
+            max_fee,
+        } = *self;
+        if max_seconds_behind.is_some_and(|max| candidate.seconds_behind > max) {
+            return Err(Constraint::MaxSecondsBehind);
+        }
+        if min_slashable_grt.is_some_and(|min| candidate.slashable_grt < min) {
+            return Err(Constraint::MinSlashableGrt);
+        }
+        if min_success_rate.is_some_and(|min| candidate.perf.success_rate.as_f64() < min) {
+            return Err(Constraint::MinSuccessRate);
+        }
+        if max_latency_ms.is_some_and(|max| candidate.perf.latency_ms > max) {
+            return Err(Constraint::MaxLatency);
+        }
+        if max_fee.is_some_and(|max| candidate.fee > max) {
+            return Err(Constraint::MaxFee);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{
+    Candidate, Constraint, Criterion, InvalidScoringConfig, InvalidSelectionOptions, Rejection,
+};
+
+/// The reason that no candidates could be selected.
+#[derive(Debug)]
+pub enum SelectionError<'c, I, D> {
+    /// No candidates were provided.
+    NoCandidates,
+    /// The scoring config is invalid. See [`ScoringConfig::validate`](crate::ScoringConfig::validate).
+    InvalidConfig(InvalidScoringConfig),
+    /// Some candidates have the same ID as an earlier candidate.
+    DuplicateIds(Vec<&'c Candidate<I, D>>),
+    /// Every candidate was rejected by the constraints.
+    AllRejected(Vec<Rejection<'c, I, D>>),
+    /// Every candidate that met the constraints has a combined score of zero.
+    AllScoredZero {
+        zero: Vec<ZeroScore<'c, I, D>>,
+        rejected: Vec<Rejection<'c, I, D>>,
+    },
+}
+
+/// Some candidates have the same ID as an earlier candidate. Each duplicate is listed in the order
+/// they were provided, without the earlier candidate that it duplicates.
+#[derive(Debug)]
+pub struct DuplicateIds<'c, I, D>(pub Vec<&'c Candidate<I, D>>);
+
+/// The reason that the inputs to a selection, such as [`select_with_config`](crate::select_with_config),
+/// are invalid.
+#[derive(Debug)]
+pub enum InvalidInput<'c, I, D> {
+    DuplicateIds(DuplicateIds<'c, I, D>),
+    InvalidConfig(InvalidScoringConfig),
+    InvalidOptions(InvalidSelectionOptions),
+}
+
+#[derive(Debug)]
+pub struct ZeroScore<'c, I, D> {
+    pub candidate: &'c Candidate<I, D>,
+    /// The criterion with the lowest score for this candidate.
+    pub criterion: Criterion,
+}
+
+impl<I, D> std::fmt::Display for SelectionError<'_, I, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoCandidates => write!(f, "no candidates available"),
+            Self::InvalidConfig(err) => err.fmt(f),
+            Self::DuplicateIds(duplicates) => {
+                write!(f, "duplicate candidate IDs ({})", duplicates.len())
+            }
+            Self::AllRejected(rejected) => {
+                write!(f, "all candidates rejected by constraints (")?;
+                let counts = [
+                    (Constraint::MaxSecondsBehind, "too far behind chain head"),
+                    (Constraint::MinSlashableGrt, "insufficient slashable GRT"),
+                    (Constraint::MinSuccessRate, "success rate too low"),
+                    (Constraint::MaxLatency, "latency too high"),
+                    (Constraint::MaxFee, "fee too high"),
+                    (Constraint::FeeBudget, "fee over budget"),
+                    (Constraint::DuplicateId, "duplicate ID"),
+                ]
+                .map(|(constraint, reason)| {
+                    let count = rejected
+                        .iter()
+                        .filter(|r| r.constraint == constraint)
+                        .count();
+                    (count, reason)
+                });
+                write_counts(f, &counts)?;
+                write!(f, ")")
+            }
+            Self::AllScoredZero { zero, rejected } => {
+                write!(f, "no candidates with a score above zero (")?;
+                let counts = [
+                    (Criterion::SuccessRate, "success rate"),
+                    (Criterion::Latency, "latency"),
+                    (Criterion::SecondsBehind, "seconds behind chain head"),
+                    (Criterion::SlashableGrt, "slashable GRT"),
+                ]
+                .map(|(criterion, reason)| {
+                    let count = zero.iter().filter(|z| z.criterion == criterion).count();
+                    (count, reason)
+                });
+                write_counts(f, &counts)?;
+                if !rejected.is_empty() {
+                    write!(f, ", {} rejected by constraints", rejected.len())?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn write_counts(f: &mut std::fmt::Formatter<'_>, counts: &[(usize, &str)]) -> std::fmt::Result {
+    let mut first = true;
+    for (count, reason) in counts.iter().filter(|(count, _)| *count > 0) {
+        if !first {
+            write!(f, ", ")?;
+        }
+        first = false;
+        write!(f, "{count}: {reason}")?;
+    }
+    Ok(())
+}
+
+impl<I, D> std::error::Error for SelectionError<'_, I, D>
+where
+    I: std::fmt::Debug,
+    D: std::fmt::Debug,
+{
+}
+
+impl<I, D> std::fmt::Display for DuplicateIds<'_, I, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "duplicate candidate IDs ({})", self.0.len())
+    }
+}
+
+impl<I, D> std::error::Error for DuplicateIds<'_, I, D>
+where
+    I: std::fmt::Debug,
+    D: std::fmt::Debug,
+{
+}
+
+impl<'c, I, D> From<DuplicateIds<'c, I, D>> for InvalidInput<'c, I, D> {
+    fn from(err: DuplicateIds<'c, I, D>) -> Self {
+        Self::DuplicateIds(err)
+    }
+}
+
+impl<I, D> From<InvalidScoringConfig> for InvalidInput<'_, I, D> {
+    fn from(err: InvalidScoringConfig) -> Self {
+        Self::InvalidConfig(err)
+    }
+}
+
+impl<I, D> From<InvalidSelectionOptions> for InvalidInput<'_, I, D> {
+    fn from(err: InvalidSelectionOptions) -> Self {
+        Self::InvalidOptions(err)
+    }
+}
+
+impl<I, D> std::fmt::Display for InvalidInput<'_, I, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateIds(err) => err.fmt(f),
+            Self::InvalidConfig(err) => err.fmt(f),
+            Self::InvalidOptions(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<I, D> std::error::Error for InvalidInput<'_, I, D>
+where
+    I: std::fmt::Debug,
+    D: std::fmt::Debug,
+{
+}
@@ -0,0 +1,39 @@
+/// How often to include a candidate with too few observations of its [`Performance`] in a
+/// selection, in addition to the candidates selected for their scores. See
+/// [`select_exploring`](crate::select_exploring).
+///
+/// [`Performance`]: crate::Performance
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Exploration {
+    /// The fraction of selections that include an under-measured candidate, when there is room
+    /// for one under the limit & budget. Values are clamped to [0, 1], and values that are not
+    /// finite are treated as 0.
+    pub epsilon: f64,
+    /// Candidates with a [`Performance::sample_count`](crate::Performance::sample_count) below
+    /// this are under-measured.
+    pub min_samples: f64,
+}
+
+impl Default for Exploration {
+    fn default() -> Self {
+        Self {
+            epsilon: 0.05,
+            min_samples: 100.0,
+        }
+    }
+}
+
+impl Exploration {
+    /// The probability of including an under-measured candidate in a selection.
+    pub(crate) fn probability(&self) -> f64 {
+        if !self.epsilon.is_finite() {
+            return 0.0;
+        }
+        self.epsilon.clamp(0.0, 1.0)
+    }
+}
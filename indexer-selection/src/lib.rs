@@ -1,15 +1,29 @@
-use std::{collections::hash_map::DefaultHasher, f64::consts::E, hash::Hasher as _};
+use std::{collections::BTreeSet, hash::Hash};
 
-pub use candidate_selection::{ArrayVec, Normalized};
+pub use candidate_selection::{
+    ArrayVec, Evaluation, Explained, InvalidSelectionOptions, Normalized, OptimalSelection, Round,
+    SearchLimits, SelectionOptions, Softmax, StopReason,
+};
+pub use constraints::*;
+pub use error::*;
+pub use exploration::*;
 pub use performance::*;
+use rand::Rng;
+pub use score::*;
 
+mod constraints;
+mod error;
+mod exploration;
 mod performance;
+mod score;
 #[cfg(test)]
 mod test;
 
 #[derive(Debug)]
 pub struct Candidate<I, D> {
-    /// The unique identifier of the candidate.
+    /// The unique identifier of the candidate. A candidate with the same ID as an earlier candidate
+    /// is rejected by [`select_constrained`], and is an error for the other selection functions.
+    /// See [`DuplicateIds`].
     pub id: I,
     /// The data associated with the candidate.
     ///
@@ -18,133 +32,493 @@ pub struct Candidate<I, D> {
     pub data: D,
 
     pub perf: ExpectedPerformance,
-    pub fee: Normalized,
+    /// The fee of the candidate, in the same units as the budget given to the selection (e.g. GRT
+    /// wei).
+    pub fee: u128,
     /// seconds behind chain head
     pub seconds_behind: u32,
     pub slashable_grt: u64,
+    /// A key for the group of indexers that tend to fail together, such as a hash of their operator
+    /// or data center. See [`ScoringConfig::group_failure_rate`].
+    pub group: Option<u64>,
 }
 
+/// Select up to `LIMIT` of the provided candidates, with a combined fee that does not exceed the
+/// `budget`.
 pub fn select<I, D, const LIMIT: usize>(
     candidates: &[Candidate<I, D>],
-) -> ArrayVec<&Candidate<I, D>, LIMIT>
+    budget: u128,
+) -> Result<ArrayVec<&Candidate<I, D>, LIMIT>, DuplicateIds<'_, I, D>>
 where
-    I: std::hash::Hash,
+    I: Ord,
 {
-    candidate_selection::select(candidates)
+    check_unique_ids(candidates)?;
+    Ok(select_unchecked(
+        candidates,
+        budget,
+        &ScoringConfig::DEFAULT,
+    ))
 }
 
-impl<I, D> candidate_selection::Candidate for Candidate<I, D>
+/// Equivalent to [`select`], but scores candidates using the given `config` instead of
+/// [`ScoringConfig::DEFAULT`]. Returns an error if the `config` is invalid. See
+/// [`ScoringConfig::validate`].
+pub fn select_with_config<'c, I, D, const LIMIT: usize>(
+    candidates: &'c [Candidate<I, D>],
+    budget: u128,
+    config: &ScoringConfig,
+) -> Result<ArrayVec<&'c Candidate<I, D>, LIMIT>, InvalidInput<'c, I, D>>
 where
-    I: std::hash::Hash,
+    I: Ord,
 {
-    type Id = u64;
+    config.validate()?;
+    check_unique_ids(candidates)?;
+    Ok(select_unchecked(candidates, budget, config))
+}
 
-    fn id(&self) -> Self::Id {
-        let mut hasher = DefaultHasher::new();
-        self.id.hash(&mut hasher);
-        hasher.finish()
-    }
+/// Equivalent to [`select_with_config`], for a valid `config` and candidates already known to
+/// have unique IDs.
+fn select_unchecked<'c, I, D, const LIMIT: usize>(
+    candidates: &'c [Candidate<I, D>],
+    budget: u128,
+    config: &ScoringConfig,
+) -> ArrayVec<&'c Candidate<I, D>, LIMIT>
+where
+    I: Ord,
+{
+    let candidates = Scored::within_budget(candidates, budget, config);
+    select_scored(&candidates, budget)
+}
 
-    fn fee(&self) -> Normalized {
-        self.fee
+/// Equivalent to [`select_with_config`], but with the given `options`, such as pinned or excluded
+/// IDs, a limit on the candidates selected from each [`Candidate::group`], or a minimum number of
+/// candidates to select. `options.fee_floor` is a fraction of the `budget`, and
+/// `options.fee_budget` is replaced by the `budget`. See [`SelectionOptions`].
+pub fn select_with_options<'c, I, D, const LIMIT: usize>(
+    candidates: &'c [Candidate<I, D>],
+    budget: u128,
+    config: &ScoringConfig,
+    options: &SelectionOptions<I>,
+) -> Result<ArrayVec<&'c Candidate<I, D>, LIMIT>, InvalidInput<'c, I, D>>
+where
+    I: Ord + Hash + Clone,
+{
+    config.validate()?;
+    check_unique_ids(candidates)?;
+    let candidates = Scored::within_budget(candidates, budget, config);
+    let options = SelectionOptions {
+        fee_budget: Some(budget),
+        ..options.clone()
+    };
+    let selected = candidate_selection::select_with_options::<_, LIMIT>(&candidates, &options)?;
+    Ok(unscored(selected))
+}
+
+#[derive(Debug)]
+pub struct ConstrainedSelection<'c, I, D, const LIMIT: usize> {
+    pub selected: ArrayVec<&'c Candidate<I, D>, LIMIT>,
+    /// The candidates excluded from selection, and the constraint that excluded each of them.
+    pub rejected: Vec<Rejection<'c, I, D>>,
+}
+
+/// Select up to `LIMIT` of the candidates that meet all of the `constraints`, with a combined fee
+/// that does not exceed the `budget`. Candidates with a fee above the budget are rejected with
+/// [`Constraint::FeeBudget`], and candidates with the same ID as an earlier candidate are rejected
+/// with [`Constraint::DuplicateId`]. Returns an error if the `config` is invalid.
+pub fn select_constrained<'c, I, D, const LIMIT: usize>(
+    candidates: &'c [Candidate<I, D>],
+    budget: u128,
+    constraints: &Constraints,
+    config: &ScoringConfig,
+) -> Result<ConstrainedSelection<'c, I, D, LIMIT>, InvalidScoringConfig>
+where
+    I: Ord,
+{
+    config.validate()?;
+    let mut rejected = Vec::new();
+    let mut ids = BTreeSet::new();
+    let accepted = candidates.iter().filter(|candidate| {
+        if !ids.insert(&candidate.id) {
+            rejected.push(Rejection {
+                candidate,
+                constraint: Constraint::DuplicateId,
+            });
+            return false;
+        }
+        let result = constraints.check(candidate).and_then(|()| {
+            if candidate.fee > budget {
+                return Err(Constraint::FeeBudget);
+            }
+            Ok(())
+        });
+        if let Err(constraint) = result {
+            rejected.push(Rejection {
+                candidate,
+                constraint,
+            });
+        }
+        result.is_ok()
+    });
+    let accepted: Vec<Scored<I, D>> = accepted
+        .map(|candidate| Scored {
+            candidate,
+            config,
+            budget,
+        })
+        .collect();
+    let selected = select_scored(&accepted, budget);
+    Ok(ConstrainedSelection { selected, rejected })
+}
+
+/// Equivalent to [`select_constrained`], but returns the reason that no candidates were selected,
+/// instead of an empty selection. Duplicate IDs are always an error.
+pub fn try_select<'c, I, D, const LIMIT: usize>(
+    candidates: &'c [Candidate<I, D>],
+    budget: u128,
+    constraints: &Constraints,
+    config: &ScoringConfig,
+) -> Result<ConstrainedSelection<'c, I, D, LIMIT>, SelectionError<'c, I, D>>
+where
+    I: Ord,
+{
+    if candidates.is_empty() {
+        return Err(SelectionError::NoCandidates);
+    }
+    let ConstrainedSelection { selected, rejected } =
+        select_constrained(candidates, budget, constraints, config)
+            .map_err(SelectionError::InvalidConfig)?;
+    let duplicates: Vec<&Candidate<I, D>> = rejected
+        .iter()
+        .filter(|r| r.constraint == Constraint::DuplicateId)
+        .map(|r| r.candidate)
+        .collect();
+    if !duplicates.is_empty() {
+        return Err(SelectionError::DuplicateIds(duplicates));
+    }
+    if !selected.is_empty() {
+        return Ok(ConstrainedSelection { selected, rejected });
+    }
+    if rejected.len() == candidates.len() {
+        return Err(SelectionError::AllRejected(rejected));
     }
+    // At least one candidate is selected if any candidate has a score above zero.
+    let zero = candidates
+        .iter()
+        .filter(|c| constraints.check(c).is_ok() && (c.fee <= budget))
+        .map(|candidate| ZeroScore {
+            candidate,
+            criterion: config.score_breakdown(candidate).lowest(),
+        })
+        .collect();
+    Err(SelectionError::AllScoredZero { zero, rejected })
+}
 
-    fn score(&self) -> Normalized {
-        [
-            score_success_rate(self.perf.success_rate),
-            score_latency(self.perf.latency_ms),
-            score_seconds_behind(self.seconds_behind),
-            score_slashable_grt(self.slashable_grt),
-        ]
+/// Equivalent to [`select_with_config`], but with the expected performance of each candidate
+/// replaced by a sample from its `performance`, using [`Performance::sample_performance`]. Candidates
+/// with few responses are selected more often than their expected performance suggests, until
+/// enough responses are observed to be confident in their performance.
+pub fn select_thompson<'c, 'p, I, D, R, const LIMIT: usize>(
+    candidates: &'c [Candidate<I, D>],
+    budget: u128,
+    config: &ScoringConfig,
+    performance: impl Fn(&Candidate<I, D>) -> &'p Performance,
+    rng: &mut R,
+) -> Result<ArrayVec<&'c Candidate<I, D>, LIMIT>, InvalidInput<'c, I, D>>
+where
+    I: Ord,
+    R: Rng + ?Sized,
+{
+    config.validate()?;
+    check_unique_ids(candidates)?;
+    // The data of each sampled candidate is its index in `candidates`.
+    let sampled: Vec<Candidate<&I, usize>> = candidates
+        .iter()
+        .enumerate()
+        .map(|(index, candidate)| Candidate {
+            id: &candidate.id,
+            data: index,
+            perf: performance(candidate).sample_performance(rng),
+            fee: candidate.fee,
+            seconds_behind: candidate.seconds_behind,
+            slashable_grt: candidate.slashable_grt,
+            group: candidate.group,
+        })
+        .collect();
+    let selected = select_unchecked::<_, _, LIMIT>(&sampled, budget, config)
         .into_iter()
-        .product()
+        .map(|c| &candidates[c.data])
+        .collect();
+    Ok(selected)
+}
+
+/// Equivalent to [`select_with_config`], but for a fraction of selections given by
+/// `exploration.epsilon`, one extra candidate is selected from those with too few observations of
+/// their `performance`. The extra candidate is only selected when the other selections leave room
+/// for it under `LIMIT` and the `budget`, and it must have a score above zero.
+pub fn select_exploring<'c, 'p, I, D, R, const LIMIT: usize>(
+    candidates: &'c [Candidate<I, D>],
+    budget: u128,
+    config: &ScoringConfig,
+    exploration: &Exploration,
+    performance: impl Fn(&Candidate<I, D>) -> &'p Performance,
+    rng: &mut R,
+) -> Result<ArrayVec<&'c Candidate<I, D>, LIMIT>, InvalidInput<'c, I, D>>
+where
+    I: Ord,
+    R: Rng + ?Sized,
+{
+    let mut selected = select_with_config::<_, _, LIMIT>(candidates, budget, config)?;
+    if selected.is_full() || !rng.gen_bool(exploration.probability()) {
+        return Ok(selected);
+    }
+    let remaining_budget = budget - selected.iter().map(|c| c.fee).sum::<u128>();
+    let under_measured: Vec<&Candidate<I, D>> = candidates
+        .iter()
+        .filter(|c| performance(c).sample_count() < exploration.min_samples)
+        .filter(|c| c.fee <= remaining_budget)
+        .filter(|c| selected.iter().all(|s| s.id != c.id))
+        .filter(|c| config.score_breakdown(c).combined > Normalized::ZERO)
+        .collect();
+    if !under_measured.is_empty() {
+        selected.push(under_measured[rng.gen_range(0..under_measured.len())]);
     }
+    Ok(selected)
+}
 
-    fn score_many<const LIMIT: usize>(candidates: &[&Self]) -> Normalized {
-        let fee = candidates.iter().map(|c| c.fee.as_f64()).sum::<f64>();
-        if Normalized::new(fee).is_none() {
-            return Normalized::ZERO;
-        }
+/// Select up to `limit` of the provided candidates, for when the limit is only known at runtime.
+pub fn select_dyn<I, D>(
+    candidates: &[Candidate<I, D>],
+    budget: u128,
+    limit: usize,
+) -> Result<Vec<&Candidate<I, D>>, DuplicateIds<'_, I, D>>
+where
+    I: Ord,
+{
+    check_unique_ids(candidates)?;
+    let candidates = Scored::within_budget(candidates, budget, &ScoringConfig::DEFAULT);
+    Ok(unscored(candidate_selection::select_dyn(
+        &candidates,
+        limit,
+        Some(budget),
+    )))
+}
 
-        // candidate latencies
-        let ls: ArrayVec<u16, LIMIT> = candidates.iter().map(|c| c.perf.latency_ms).collect();
-        // probability of candidate responses returning to client, based on `ls`
-        let ps = {
-            let mut ps: ArrayVec<Normalized, LIMIT> =
-                candidates.iter().map(|c| c.perf.success_rate).collect();
-            let mut ls = ls.clone();
-            let mut sort = permutation::sort_unstable(&mut ls);
-            sort.apply_slice_in_place(&mut ls);
-            sort.apply_slice_in_place(&mut ps);
-            let pf: ArrayVec<f64, LIMIT> = ps
-                .iter()
-                .map(|p| 1.0 - p.as_f64())
-                .scan(1.0, |s, x| {
-                    *s *= x;
-                    Some(*s)
-                })
-                .collect();
-            let mut ps: ArrayVec<f64, LIMIT> = std::iter::once(&1.0)
-                .chain(&pf)
-                .take(LIMIT)
-                .zip(&ps)
-                .map(|(&p, &s)| p * s.as_f64())
-                .collect();
-            sort.inverse().apply_slice_in_place(&mut ps);
-            ps
-        };
-
-        let success_rate = Normalized::new(ps.iter().sum()).unwrap_or(Normalized::ONE);
-        let latency = candidates
-            .iter()
-            .map(|c| c.perf.latency_ms as f64)
-            .zip(&ps)
-            .map(|(x, p)| x.recip() * p)
-            .sum::<f64>()
-            .recip() as u16;
-        let seconds_behind = candidates.iter().map(|c| c.seconds_behind).max().unwrap();
-        let slashable_grt = candidates.iter().map(|c| c.slashable_grt).min().unwrap();
-
-        [
-            score_success_rate(success_rate),
-            score_latency(latency),
-            score_seconds_behind(seconds_behind),
-            score_slashable_grt(slashable_grt),
-        ]
+/// Equivalent to [`select`], but also returns the trace of decisions made by the selection. See
+/// [`candidate_selection::select_explained`]. The fees in the trace are fractions of the `budget`.
+pub fn select_explained<I, D, const LIMIT: usize>(
+    candidates: &[Candidate<I, D>],
+    budget: u128,
+) -> Result<Explained<'_, Candidate<I, D>, I, LIMIT>, DuplicateIds<'_, I, D>>
+where
+    I: Ord + Clone,
+{
+    check_unique_ids(candidates)?;
+    let candidates = Scored::within_budget(candidates, budget, &ScoringConfig::DEFAULT);
+    let Explained {
+        selected,
+        rounds,
+        stop,
+    } = candidate_selection::select_explained::<_, LIMIT>(&candidates, Some(budget));
+    // The trace borrows the IDs from the scored candidates, which are dropped on return.
+    let rounds = rounds
         .into_iter()
-        .product()
+        .map(|round| Round {
+            evaluations: round
+                .evaluations
+                .into_iter()
+                .map(|evaluation| Evaluation {
+                    id: evaluation.id.clone(),
+                    marginal_score: evaluation.marginal_score,
+                    fee: evaluation.fee,
+                    fee_adjusted_score: evaluation.fee_adjusted_score,
+                })
+                .collect(),
+            selected: round.selected.cloned(),
+        })
+        .collect();
+    Ok(Explained {
+        selected: unscored(selected),
+        rounds,
+        stop,
+    })
+}
+
+/// Select the best set of up to `LIMIT` candidates, exhaustively within the given `limits`. See
+/// [`candidate_selection::select_optimal`].
+pub fn select_optimal<I, D, const LIMIT: usize>(
+    candidates: &[Candidate<I, D>],
+    budget: u128,
+    limits: SearchLimits,
+) -> Result<OptimalSelection<'_, Candidate<I, D>, LIMIT>, DuplicateIds<'_, I, D>>
+where
+    I: Ord,
+{
+    check_unique_ids(candidates)?;
+    let candidates = Scored::within_budget(candidates, budget, &ScoringConfig::DEFAULT);
+    let OptimalSelection {
+        selected,
+        score,
+        complete,
+        nodes,
+    } = candidate_selection::select_optimal::<_, LIMIT>(&candidates, Some(budget), limits);
+    Ok(OptimalSelection {
+        selected: unscored(selected),
+        score,
+        complete,
+        nodes,
+    })
+}
+
+/// The scores use [`ScoringConfig::DEFAULT`].
+impl<I, D> Candidate<I, D> {
+    pub fn score(&self) -> Normalized {
+        self.score_breakdown().combined
+    }
+
+    pub fn score_breakdown(&self) -> ScoreBreakdown {
+        ScoringConfig::DEFAULT.score_breakdown(self)
+    }
+
+    /// The breakdown of the combined score of a set of candidates, or `None` if their combined fee
+    /// exceeds the `budget`.
+    pub fn score_many_breakdown<const LIMIT: usize>(
+        candidates: &[&Self],
+        budget: u128,
+    ) -> Option<ScoreBreakdown> {
+        ScoringConfig::DEFAULT.score_many_breakdown::<I, D, LIMIT>(candidates, budget)
+    }
+
+    /// Equivalent to `score_many_breakdown`, for sets of candidates with a size only known at
+    /// runtime.
+    pub fn score_many_breakdown_dyn(candidates: &[&Self], budget: u128) -> Option<ScoreBreakdown> {
+        ScoringConfig::DEFAULT.score_many_breakdown_dyn(candidates, budget)
     }
 }
 
-// When picking curves to use consider the following reference:
-// https://en.wikipedia.org/wiki/Logistic_function
+/// A candidate scored using a `ScoringConfig`, with its fee as a fraction of the budget.
+struct Scored<'c, 's, I, D> {
+    candidate: &'c Candidate<I, D>,
+    config: &'s ScoringConfig,
+    budget: u128,
+}
 
-/// https://www.desmos.com/calculator/jdogbfxw2j
-fn score_seconds_behind(seconds_behind: u32) -> Normalized {
-    let b: f64 = 1e-16;
-    let l: f64 = 1.532;
-    let k: f64 = 0.021;
-    let x_0: i64 = 30;
-    let u = b + (l / (1.0 + E.powf(k * (seconds_behind as i64 - x_0) as f64)));
-    Normalized::new(u).unwrap()
+impl<'c, 's, I, D> Scored<'c, 's, I, D> {
+    /// Wrap the candidates that could be selected without exceeding the `budget`.
+    fn within_budget(
+        candidates: impl IntoIterator<Item = &'c Candidate<I, D>>,
+        budget: u128,
+        config: &'s ScoringConfig,
+    ) -> Vec<Self>
+    where
+        I: 'c,
+        D: 'c,
+    {
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.fee <= budget)
+            .map(|candidate| Self {
+                candidate,
+                config,
+                budget,
+            })
+            .collect()
+    }
 }
 
-/// https://www.desmos.com/calculator/iqhjcdnphv
-fn score_slashable_grt(slashable_grt: u64) -> Normalized {
-    let x = slashable_grt as f64;
-    // Currently setting a minimum score of ~0.8 at the minimum stake requirement of 100,000 GRT.
-    let a = 1.6e-5;
-    Normalized::new(1.0 - E.powf(-a * x)).unwrap()
+/// Select up to `LIMIT` of the scored candidates, with a combined fee that does not exceed the
+/// `budget`. This is equivalent to [`candidate_selection::select`], which has no integer budget.
+fn select_scored<'a, 'c, 's, I, D, const LIMIT: usize>(
+    candidates: &'a [Scored<'c, 's, I, D>],
+    budget: u128,
+) -> ArrayVec<&'c Candidate<I, D>, LIMIT>
+where
+    I: Ord,
+{
+    unscored(candidate_selection::select_dyn(
+        candidates,
+        LIMIT,
+        Some(budget),
+    ))
 }
 
-/// https://www.desmos.com/calculator/v2vrfktlpl
-pub fn score_latency(latency_ms: u16) -> Normalized {
-    let s = |x: u16| 1.0 + E.powf(((x as f64) - 400.0) / 300.0);
-    // Since high latency becomes bad success rate via timeouts, latency scores should have a floor.
-    Normalized::clamp(s(0) / s(latency_ms), 0.001, 1.0).unwrap()
+/// Return the candidates with the same ID as an earlier candidate, if any.
+fn check_unique_ids<I, D>(candidates: &[Candidate<I, D>]) -> Result<(), DuplicateIds<'_, I, D>>
+where
+    I: Ord,
+{
+    let mut ids = BTreeSet::new();
+    let duplicates: Vec<&Candidate<I, D>> = candidates
+        .iter()
+        .filter(|candidate| !ids.insert(&candidate.id))
+        .collect();
+    if !duplicates.is_empty() {
+        return Err(DuplicateIds(duplicates));
+    }
+    Ok(())
+}
+
+/// Map a selection of scored candidates back to the candidates themselves.
+fn unscored<'a, 'c: 'a, 's: 'a, I: 'c, D: 'c, T>(
+    selected: impl IntoIterator<Item = &'a Scored<'c, 's, I, D>>,
+) -> T
+where
+    T: FromIterator<&'c Candidate<I, D>>,
+{
+    selected.into_iter().map(|c| c.candidate).collect()
 }
 
-/// https://www.desmos.com/calculator/df2keku3ad
-fn score_success_rate(success_rate: Normalized) -> Normalized {
-    Normalized::clamp(success_rate.as_f64().powi(7), 1e-8, 1.0).unwrap()
+impl<I, D> candidate_selection::Candidate for Scored<'_, '_, I, D>
+where
+    I: Ord,
+{
+    type Id = I;
+
+    fn id(&self) -> &Self::Id {
+        &self.candidate.id
+    }
+
+    fn group(&self) -> Option<u64> {
+        self.candidate.group
+    }
+
+    fn fee(&self) -> Normalized {
+        if self.candidate.fee == 0 {
+            return Normalized::ZERO;
+        }
+        Normalized::new(self.candidate.fee as f64 / self.budget as f64).unwrap()
+    }
+
+    fn fee_units(&self) -> Option<u128> {
+        Some(self.candidate.fee)
+    }
+
+    fn score(&self) -> Normalized {
+        self.config.score_breakdown(self.candidate).combined
+    }
+
+    fn score_many<const LIMIT: usize>(candidates: &[&Self]) -> Normalized {
+        let Self { config, budget, .. } = *candidates[0];
+        let candidates: ArrayVec<&Candidate<I, D>, LIMIT> =
+            candidates.iter().map(|c| c.candidate).collect();
+        config
+            .score_many_breakdown::<I, D, LIMIT>(&candidates, budget)
+            .map(|b| b.combined)
+            .unwrap_or(Normalized::ZERO)
+    }
+
+    fn score_many_dyn(candidates: &[&Self]) -> Normalized {
+        let Self { config, budget, .. } = *candidates[0];
+        let candidates: Vec<&Candidate<I, D>> = candidates.iter().map(|c| c.candidate).collect();
+        config
+            .score_many_breakdown_dyn(&candidates, budget)
+            .map(|b| b.combined)
+            .unwrap_or(Normalized::ZERO)
+    }
+
+    fn score_bound<const LIMIT: usize>(candidates: &[&Self]) -> Normalized {
+        let config = candidates[0].config;
+        let candidates: ArrayVec<&Candidate<I, D>, LIMIT> =
+            candidates.iter().map(|c| c.candidate).collect();
+        config.score_bound(&candidates)
+    }
 }
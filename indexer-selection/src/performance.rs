@@ -1,14 +1,409 @@
+use std::time::{Duration, Instant, SystemTime};
+
 use candidate_selection::Normalized;
+use rand::Rng;
+use rand_distr::{Beta, Distribution as _, Gamma};
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "VersionedPerformance", from = "VersionedPerformance")
+)]
 pub struct Performance {
     fast: ShortTerm,
     slow: LongTerm,
+    /// The responses observed locally since the last call to [`Performance::export_delta`]. These
+    /// are not serialized or included in snapshots, since a restored instance may not be the one
+    /// that observed them.
+    unshared: PerformanceDelta,
+    /// The time up to which decay has been applied by [`Performance::decay_until`].
+    decayed_until: Option<Instant>,
+    config: PerformanceConfig,
+}
+
+/// The serialized format of [`Performance`]. New versions must be added as new variants, so that
+/// older formats can still be deserialized. Only the latest version is serialized, and older
+/// versions are migrated to it when deserialized.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "version")]
+enum VersionedPerformance {
+    #[serde(rename = "1", skip_serializing)]
+    V1 {
+        fast: ShortTerm,
+        slow: LongTermV1,
+        config: PerformanceConfig,
+    },
+    #[serde(rename = "2")]
+    V2 {
+        fast: ShortTerm,
+        slow: LongTerm,
+        config: PerformanceConfig,
+    },
+}
+
+/// The format of [`LongTerm`] in [`VersionedPerformance::V1`], before failures were counted by
+/// kind.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct LongTermV1 {
+    latency_hist: [f32; 29],
+    failure_count: f64,
+}
+
+#[cfg(feature = "serde")]
+impl From<LongTermV1> for LongTerm {
+    /// All failures in V1 were weighted equally, so they are migrated as [`FailureKind::Other`].
+    fn from(slow: LongTermV1) -> Self {
+        let LongTermV1 {
+            latency_hist,
+            failure_count,
+        } = slow;
+        let mut failure_kinds = [0.0; FailureKind::ALL.len()];
+        failure_kinds[FailureKind::Other as usize] = failure_count;
+        Self {
+            latency_hist,
+            failure_count,
+            failure_kinds,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Performance> for VersionedPerformance {
+    fn from(performance: Performance) -> Self {
+        let Performance {
+            fast, slow, config, ..
+        } = performance;
+        Self::V2 { fast, slow, config }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<VersionedPerformance> for Performance {
+    fn from(performance: VersionedPerformance) -> Self {
+        match performance {
+            VersionedPerformance::V1 { fast, slow, config } => VersionedPerformance::V2 {
+                fast,
+                slow: slow.into(),
+                config,
+            }
+            .into(),
+            VersionedPerformance::V2 { fast, slow, config } => Self {
+                fast,
+                slow,
+                unshared: PerformanceDelta::default(),
+                decayed_until: None,
+                config,
+            },
+        }
+    }
+}
+
+/// A copy of a [`Performance`] at some point in time, to be restored later, such as after a
+/// restart.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PerformanceSnapshot {
+    pub taken_at: SystemTime,
+    performance: Performance,
+}
+
+impl PerformanceSnapshot {
+    /// Restore the [`Performance`], with the decay applied for the time elapsed between
+    /// `taken_at` and `now`.
+    pub fn restore(self, now: SystemTime) -> Performance {
+        let mut performance = self.performance;
+        performance.decay(now.duration_since(self.taken_at).unwrap_or_default());
+        performance
+    }
+}
+
+/// Responses observed by one [`Performance`], to be merged into others. For example, replicas may
+/// periodically exchange their deltas so that each learns from the responses observed by all of
+/// them. See [`Performance::export_delta`] and [`Performance::merge`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PerformanceDelta {
+    fast: ShortTerm,
+    slow: LongTerm,
+}
+
+impl PerformanceDelta {
+    /// Combine the responses of `other` into `self`, so that they may be exported together.
+    /// Merging is commutative and associative.
+    pub fn merge(&mut self, other: &Self) {
+        self.fast.merge(&other.fast);
+        self.slow.merge(&other.slow);
+    }
+
+    fn feedback(&mut self, outcome: Outcome, failure_weight: f64, latency_ms: u16) {
+        self.fast.feedback(outcome, failure_weight, latency_ms);
+        self.slow.feedback(outcome, failure_weight, latency_ms);
+    }
+
+    fn decay(&mut self, config: &PerformanceConfig, secs: f64) {
+        self.fast.decay(config.fast_decay_hz, secs);
+        self.slow.decay(config.slow_decay_hz, secs);
+    }
 }
 
-const FAST_BIAS: f64 = 0.8;
-const FAST_DECAY_HZ: f64 = 0.05;
-const SLOW_DECAY_HZ: f64 = 0.001;
+/// The parameters of how responses are combined into a [`Performance`]. The config is validated
+/// when deserialized, including as part of a [`Performance`] or [`PerformanceSnapshot`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "UnvalidatedPerformanceConfig")
+)]
+pub struct PerformanceConfig {
+    /// The weight of the short-term performance, relative to the long-term performance. Must be
+    /// in [0, 1].
+    pub fast_bias: f64,
+    /// The fraction of the short-term performance forgotten per second. Must be in (0, 1).
+    pub fast_decay_hz: f64,
+    /// The fraction of the long-term performance forgotten per second. Must be in (0, 1).
+    pub slow_decay_hz: f64,
+    /// Success rates are scaled by this, so that no individual indexer is expected to always
+    /// succeed. Must be in [0, 1].
+    pub max_success_rate: f64,
+    /// The percentile of the long-term latencies used as the long-term latency. Must be in
+    /// [1, 99].
+    pub latency_percentile: u8,
+    /// How much each kind of failure counts against the success rate.
+    pub failure_weights: FailureWeights,
+}
+
+impl PerformanceConfig {
+    pub const DEFAULT: Self = Self {
+        fast_bias: 0.8,
+        fast_decay_hz: 0.05,
+        slow_decay_hz: 0.001,
+        max_success_rate: 0.99,
+        latency_percentile: 99,
+        failure_weights: FailureWeights::DEFAULT,
+    };
+
+    pub fn validate(&self) -> Result<(), InvalidPerformanceConfig> {
+        if !(0.0..=1.0).contains(&self.fast_bias) {
+            return Err(InvalidPerformanceConfig::FastBias);
+        }
+        let valid_decay_hz = |rate_hz: f64| (0.0 < rate_hz) && (rate_hz < 1.0);
+        if !valid_decay_hz(self.fast_decay_hz) || !valid_decay_hz(self.slow_decay_hz) {
+            return Err(InvalidPerformanceConfig::DecayHz);
+        }
+        if !(0.0..=1.0).contains(&self.max_success_rate) {
+            return Err(InvalidPerformanceConfig::MaxSuccessRate);
+        }
+        if !(1..=99).contains(&self.latency_percentile) {
+            return Err(InvalidPerformanceConfig::LatencyPercentile);
+        }
+        if FailureKind::ALL
+            .iter()
+            .any(|kind| !(0.0..=1.0).contains(&self.failure_weights.weight(*kind)))
+        {
+            return Err(InvalidPerformanceConfig::FailureWeight);
+        }
+        Ok(())
+    }
+
+    fn combine_success_rate(&self, fast: f64, slow: f64) -> Normalized {
+        let success_rate = (fast * self.fast_bias) + (slow * (1.0 - self.fast_bias));
+        Normalized::new(success_rate * self.max_success_rate).unwrap()
+    }
+
+    fn combine_latency(&self, fast: f64, slow: f64) -> u16 {
+        ((fast * self.fast_bias) + (slow * (1.0 - self.fast_bias))) as u16
+    }
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The fields of a [`PerformanceConfig`], as deserialized before validation.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(default)]
+struct UnvalidatedPerformanceConfig {
+    fast_bias: f64,
+    fast_decay_hz: f64,
+    slow_decay_hz: f64,
+    max_success_rate: f64,
+    latency_percentile: u8,
+    failure_weights: FailureWeights,
+}
+
+#[cfg(feature = "serde")]
+impl Default for UnvalidatedPerformanceConfig {
+    fn default() -> Self {
+        let PerformanceConfig {
+            fast_bias,
+            fast_decay_hz,
+            slow_decay_hz,
+            max_success_rate,
+            latency_percentile,
+            failure_weights,
+        } = PerformanceConfig::DEFAULT;
+        Self {
+            fast_bias,
+            fast_decay_hz,
+            slow_decay_hz,
+            max_success_rate,
+            latency_percentile,
+            failure_weights,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UnvalidatedPerformanceConfig> for PerformanceConfig {
+    type Error = InvalidPerformanceConfig;
+
+    fn try_from(config: UnvalidatedPerformanceConfig) -> Result<Self, Self::Error> {
+        let UnvalidatedPerformanceConfig {
+            fast_bias,
+            fast_decay_hz,
+            slow_decay_hz,
+            max_success_rate,
+            latency_percentile,
+            failure_weights,
+        } = config;
+        let config = Self {
+            fast_bias,
+            fast_decay_hz,
+            slow_decay_hz,
+            max_success_rate,
+            latency_percentile,
+            failure_weights,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidPerformanceConfig {
+    FastBias,
+    DecayHz,
+    MaxSuccessRate,
+    LatencyPercentile,
+    FailureWeight,
+}
+
+impl std::fmt::Display for InvalidPerformanceConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FastBias => write!(f, "fast_bias must be in [0, 1]"),
+            Self::DecayHz => write!(f, "decay rates must be in (0, 1)"),
+            Self::MaxSuccessRate => write!(f, "max_success_rate must be in [0, 1]"),
+            Self::LatencyPercentile => write!(f, "latency_percentile must be in [1, 99]"),
+            Self::FailureWeight => write!(f, "failure_weights must be in [0, 1]"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidPerformanceConfig {}
+
+/// The outcome of a query sent to an indexer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure(FailureKind),
+}
+
+impl From<bool> for Outcome {
+    /// Failures are of kind [`FailureKind::Other`].
+    fn from(success: bool) -> Self {
+        match success {
+            true => Self::Success,
+            false => Self::Failure(FailureKind::Other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FailureKind {
+    /// No response was received in time.
+    Timeout,
+    /// The response had an unexpected HTTP status, or could not be read.
+    Http,
+    /// The response attestation was missing or invalid.
+    BadAttestation,
+    /// The response was for a block too far behind the one requested.
+    Stale,
+    /// The indexer failed to execute the query.
+    IndexerError,
+    /// The query itself is invalid, such that any indexer would have failed to execute it.
+    BadQuery,
+    /// Any other failure, including failures reported without a kind.
+    Other,
+}
+
+impl FailureKind {
+    pub const ALL: [Self; 7] = [
+        Self::Timeout,
+        Self::Http,
+        Self::BadAttestation,
+        Self::Stale,
+        Self::IndexerError,
+        Self::BadQuery,
+        Self::Other,
+    ];
+}
+
+/// How much each [`FailureKind`] counts against the success rate of an indexer. A failure with
+/// weight 1 counts as a full failure, and the rest of a failure with a lower weight is left out of
+/// the success rate, so that a failure with weight 0 counts as neither a success nor a failure.
+/// Each weight must be in [0, 1].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct FailureWeights {
+    pub timeout: f64,
+    pub http: f64,
+    pub bad_attestation: f64,
+    pub stale: f64,
+    pub indexer_error: f64,
+    pub bad_query: f64,
+    pub other: f64,
+}
+
+impl FailureWeights {
+    pub const DEFAULT: Self = Self {
+        timeout: 1.0,
+        http: 1.0,
+        bad_attestation: 1.0,
+        stale: 1.0,
+        indexer_error: 1.0,
+        bad_query: 0.0,
+        other: 1.0,
+    };
+
+    pub fn weight(&self, kind: FailureKind) -> f64 {
+        match kind {
+            FailureKind::Timeout => self.timeout,
+            FailureKind::Http => self.http,
+            FailureKind::BadAttestation => self.bad_attestation,
+            FailureKind::Stale => self.stale,
+            FailureKind::IndexerError => self.indexer_error,
+            FailureKind::BadQuery => self.bad_query,
+            FailureKind::Other => self.other,
+        }
+    }
+}
+
+impl Default for FailureWeights {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
 
 #[derive(Clone, Copy, Debug)]
 pub struct ExpectedPerformance {
@@ -17,39 +412,198 @@ pub struct ExpectedPerformance {
 }
 
 impl Performance {
+    /// # Panics
+    /// If the `config` is invalid. See [`PerformanceConfig::validate`].
+    pub fn new(config: PerformanceConfig) -> Self {
+        config.validate().unwrap();
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> &PerformanceConfig {
+        &self.config
+    }
+
+    /// Take a snapshot of the performance at time `taken_at`, with the decay up to `now` applied
+    /// (see [`Performance::decay_until`]) without modifying `self`. `now` should be the same point
+    /// in time as `taken_at`. Responses not yet exported by [`Performance::export_delta`] are not
+    /// included, so that they are not shared again by the restored instance.
+    pub fn snapshot(&self, taken_at: SystemTime, now: Instant) -> PerformanceSnapshot {
+        let mut performance = self.clone();
+        performance.decay_until(now);
+        performance.unshared = PerformanceDelta::default();
+        performance.decayed_until = None;
+        PerformanceSnapshot {
+            taken_at,
+            performance,
+        }
+    }
+
+    /// The expected performance given the responses observed so far. This does not include decay
+    /// pending since the last call to [`Performance::decay_until`], so use
+    /// [`Performance::expected_performance_at`] when using lazy decay.
     pub fn expected_performance(&self) -> ExpectedPerformance {
+        self.decayed_performance(0.0)
+    }
+
+    /// A sample from the posterior distributions of the success rate & latency, given the
+    /// responses observed so far. Unlike [`Performance::expected_performance`], samples for
+    /// candidates with few responses vary more than for candidates with many responses. This is
+    /// used for [Thompson sampling](https://en.wikipedia.org/wiki/Thompson_sampling). Like
+    /// [`Performance::expected_performance`], this does not include pending decay.
+    pub fn sample_performance<R: Rng + ?Sized>(&self, rng: &mut R) -> ExpectedPerformance {
         ExpectedPerformance {
-            success_rate: self.success_rate(),
-            latency_ms: self.latency_ms(),
+            success_rate: self.config.combine_success_rate(
+                self.fast.sample_success_rate(rng),
+                self.slow.sample_success_rate(rng),
+            ),
+            latency_ms: self.config.combine_latency(
+                self.fast.sample_latency_ms(rng),
+                self.slow
+                    .sample_latency_percentile(self.config.latency_percentile, rng),
+            ),
         }
     }
 
+    /// The number of responses observed, as reduced by the decay applied so far.
+    pub fn sample_count(&self) -> f64 {
+        self.slow.latency_hist.iter().map(|c| *c as f64).sum()
+    }
+
+    /// The number of failures of the given kind observed, as reduced by the decay applied so far.
+    pub fn failure_count(&self, kind: FailureKind) -> f64 {
+        self.slow.failure_kinds[kind as usize]
+    }
+
+    /// Equivalent to [`Performance::feedback_outcome`], where failures are of kind
+    /// [`FailureKind::Other`]. Use [`Performance::feedback_at`] when using lazy decay.
     pub fn feedback(&mut self, success: bool, latency_ms: u16) {
-        self.fast.feedback(success, latency_ms);
-        self.slow.feedback(success, latency_ms);
+        self.feedback_outcome(success.into(), latency_ms);
+    }
+
+    /// Record the outcome of a query. Failures count against the success rate according to
+    /// [`PerformanceConfig::failure_weights`]. This does not apply pending decay, so use
+    /// [`Performance::feedback_outcome_at`] when using lazy decay.
+    pub fn feedback_outcome(&mut self, outcome: Outcome, latency_ms: u16) {
+        let failure_weight = match outcome {
+            Outcome::Success => 0.0,
+            Outcome::Failure(kind) => self.config.failure_weights.weight(kind),
+        };
+        self.fast.feedback(outcome, failure_weight, latency_ms);
+        self.slow.feedback(outcome, failure_weight, latency_ms);
+        self.unshared.feedback(outcome, failure_weight, latency_ms);
+    }
+
+    /// Take the responses observed locally since the last export, for other instances to
+    /// [`Performance::merge`]. Responses merged from other instances are never exported again, so
+    /// that no response is counted twice.
+    pub fn export_delta(&mut self) -> PerformanceDelta {
+        std::mem::take(&mut self.unshared)
+    }
+
+    /// Add the responses exported by another instance. Merging is commutative and associative, so
+    /// replicas converge regardless of the order in which deltas are received. Each delta must be
+    /// merged at most once, and never into the instance that exported it.
+    pub fn merge(&mut self, delta: &PerformanceDelta) {
+        self.fast.merge(&delta.fast);
+        self.slow.merge(&delta.slow);
+    }
+
+    /// Equivalent to [`Performance::merge`], after applying the decay up to `now` (see
+    /// [`Performance::decay_until`]).
+    pub fn merge_at(&mut self, now: Instant, delta: &PerformanceDelta) {
+        self.decay_until(now);
+        self.merge(delta);
+    }
+
+    /// Equivalent to [`Performance::expected_performance`], after applying the decay up to `now`
+    /// (see [`Performance::decay_until`]) without modifying `self`.
+    pub fn expected_performance_at(&self, now: Instant) -> ExpectedPerformance {
+        let elapsed = match self.decayed_until {
+            Some(decayed_until) => now.saturating_duration_since(decayed_until),
+            None => Duration::ZERO,
+        };
+        self.decayed_performance(elapsed.as_secs_f64())
+    }
+
+    /// Equivalent to [`Performance::feedback`], after applying the decay up to `now` (see
+    /// [`Performance::decay_until`]).
+    pub fn feedback_at(&mut self, now: Instant, success: bool, latency_ms: u16) {
+        self.feedback_outcome_at(now, success.into(), latency_ms);
+    }
+
+    /// Equivalent to [`Performance::feedback_outcome`], after applying the decay up to `now` (see
+    /// [`Performance::decay_until`]).
+    pub fn feedback_outcome_at(&mut self, now: Instant, outcome: Outcome, latency_ms: u16) {
+        self.decay_until(now);
+        self.feedback_outcome(outcome, latency_ms);
     }
 
-    pub fn decay(&mut self) {
-        self.fast.decay(FAST_DECAY_HZ);
-        self.slow.decay(SLOW_DECAY_HZ);
+    /// Reduce the weight of past responses, as though `elapsed` time has passed. The decay rates
+    /// are per second, so that for each second the counts are multiplied by `1 - rate`.
+    pub fn decay(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        self.fast.decay(self.config.fast_decay_hz, secs);
+        self.slow.decay(self.config.slow_decay_hz, secs);
+        self.unshared.decay(&self.config, secs);
     }
 
-    fn success_rate(&self) -> Normalized {
-        let fast = self.fast.success_rate();
-        let slow = self.slow.success_rate();
-        let success_rate = (fast * FAST_BIAS) + (slow * (1.0 - FAST_BIAS));
-        // limit an individual indexer's success rate to 99%
-        Normalized::new(success_rate * 0.99).unwrap()
+    /// Apply the decay for the time elapsed since the last call to this function, so that no
+    /// periodic call to [`Performance::decay`] is required. The first call only records `now`.
+    /// Times earlier than the last call are ignored.
+    pub fn decay_until(&mut self, now: Instant) {
+        if let Some(decayed_until) = self.decayed_until {
+            self.decay(now.saturating_duration_since(decayed_until));
+        }
+        self.decayed_until = Some(self.decayed_until.map_or(now, |t| t.max(now)));
     }
 
-    fn latency_ms(&self) -> u16 {
-        let fast = self.fast.latency_ms() as f64;
-        let slow = self.slow.latency_percentile(99) as f64;
-        ((fast * FAST_BIAS) + (slow * (1.0 - FAST_BIAS))) as u16
+    /// The expected performance, as though the decay for `secs` had been applied.
+    fn decayed_performance(&self, secs: f64) -> ExpectedPerformance {
+        let fast_retain = retain(self.config.fast_decay_hz, secs);
+        let slow_retain = retain(self.config.slow_decay_hz, secs);
+        let success_rate = self.config.combine_success_rate(
+            self.fast.success_rate(fast_retain),
+            self.slow.success_rate(slow_retain),
+        );
+        // Decay scales every bin of the histogram equally, so the percentile is unchanged.
+        let latency_ms = self.config.combine_latency(
+            self.fast.latency_ms(fast_retain) as f64,
+            self.slow.latency_percentile(self.config.latency_percentile) as f64,
+        );
+        ExpectedPerformance {
+            success_rate,
+            latency_ms,
+        }
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[cfg(test)]
+impl Performance {
+    /// Whether both have observed the same responses, as reduced by decay. Their configs, pending
+    /// decay, and unshared responses are not compared.
+    pub(crate) fn same_responses(&self, other: &Self) -> bool {
+        (self.fast == other.fast) && (self.slow == other.slow)
+    }
+}
+
+/// The fraction of counts retained after decaying at `rate_hz` for `secs`.
+fn retain(rate_hz: f64, secs: f64) -> f64 {
+    debug_assert!((0.0 < rate_hz) && (rate_hz < 1.0));
+    (1.0 - rate_hz).powf(secs)
+}
+
+/// Sample the success rate from its posterior, a beta distribution with a uniform prior.
+fn sample_success_rate<R: Rng + ?Sized>(successes: f64, failures: f64, rng: &mut R) -> f64 {
+    Beta::new(successes.max(0.0) + 1.0, failures.max(0.0) + 1.0)
+        .unwrap()
+        .sample(rng)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct ShortTerm {
     total_latency_ms: f64,
     success_count: f64,
@@ -57,41 +611,70 @@ struct ShortTerm {
 }
 
 impl ShortTerm {
-    fn decay(&mut self, rate_hz: f64) {
-        debug_assert!((0.0 < rate_hz) && (rate_hz < 1.0));
-        let retain = 1.0 - rate_hz;
+    fn decay(&mut self, rate_hz: f64, secs: f64) {
+        let retain = retain(rate_hz, secs);
         self.total_latency_ms *= retain;
         self.success_count *= retain;
         self.failure_count *= retain;
     }
 
-    fn feedback(&mut self, success: bool, latency_ms: u16) {
-        self.total_latency_ms += latency_ms as f64;
-        if success {
-            self.success_count += 1.0;
-        } else {
-            self.failure_count += 1.0;
-        }
+    fn merge(&mut self, other: &Self) {
+        self.total_latency_ms += other.total_latency_ms;
+        self.success_count += other.success_count;
+        self.failure_count += other.failure_count;
+    }
+
+    /// Failures are counted by their weight, including in the average latency.
+    fn feedback(&mut self, outcome: Outcome, failure_weight: f64, latency_ms: u16) {
+        let weight = match outcome {
+            Outcome::Success => {
+                self.success_count += 1.0;
+                1.0
+            }
+            Outcome::Failure(_) => {
+                self.failure_count += failure_weight;
+                failure_weight
+            }
+        };
+        self.total_latency_ms += weight * latency_ms as f64;
     }
 
-    fn success_rate(&self) -> f64 {
+    /// The success rate, with the counts scaled by `retain` for pending decay.
+    fn success_rate(&self, retain: f64) -> f64 {
         // add 1 to pull success rate upward, and avoid divide by zero
-        let s = self.success_count + 1.0;
-        let f = self.failure_count;
+        let s = (self.success_count * retain) + 1.0;
+        let f = self.failure_count * retain;
         s / (s + f)
     }
 
-    fn latency_ms(&self) -> u16 {
-        let responses = self.success_count + self.failure_count;
-        let avg_latency_ms = self.total_latency_ms / responses.max(1.0);
+    /// The average latency, with the counts scaled by `retain` for pending decay.
+    fn latency_ms(&self, retain: f64) -> u16 {
+        let responses = (self.success_count + self.failure_count) * retain;
+        let avg_latency_ms = (self.total_latency_ms * retain) / responses.max(1.0);
         avg_latency_ms as u16
     }
+
+    fn sample_success_rate<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        sample_success_rate(self.success_count, self.failure_count, rng)
+    }
+
+    fn sample_latency_ms<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        // Latencies are modeled as exponentially distributed. So the posterior of the mean latency
+        // is the total latency divided by a gamma distribution, shaped by the response count.
+        let responses = self.success_count + self.failure_count;
+        let responses = Gamma::new(responses + 1.0, 1.0).unwrap().sample(rng);
+        self.total_latency_ms / responses
+    }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct LongTerm {
     latency_hist: [f32; 29],
+    /// The failures, weighted by [`FailureWeights`].
     failure_count: f64,
+    /// The unweighted failures, indexed by [`FailureKind`].
+    failure_kinds: [f64; FailureKind::ALL.len()],
 }
 
 const LATENCY_BINS: [u16; 29] = [
@@ -106,18 +689,31 @@ const LATENCY_BINS: [u16; 29] = [
 ];
 
 impl LongTerm {
-    fn decay(&mut self, rate_hz: f64) {
-        debug_assert!((0.0 < rate_hz) && (rate_hz < 1.0));
-        let retain = 1.0 - rate_hz;
+    fn decay(&mut self, rate_hz: f64, secs: f64) {
+        let retain = retain(rate_hz, secs);
         self.failure_count *= retain;
+        for count in &mut self.failure_kinds {
+            *count *= retain;
+        }
         for count in &mut self.latency_hist {
             *count *= retain as f32;
         }
     }
 
-    fn feedback(&mut self, success: bool, latency_ms: u16) {
-        if !success {
-            self.failure_count += 1.0;
+    fn merge(&mut self, other: &Self) {
+        self.failure_count += other.failure_count;
+        for (count, other) in self.failure_kinds.iter_mut().zip(&other.failure_kinds) {
+            *count += *other;
+        }
+        for (count, other) in self.latency_hist.iter_mut().zip(&other.latency_hist) {
+            *count += *other;
+        }
+    }
+
+    fn feedback(&mut self, outcome: Outcome, failure_weight: f64, latency_ms: u16) {
+        self.failure_count += failure_weight;
+        if let Outcome::Failure(kind) = outcome {
+            self.failure_kinds[kind as usize] += 1.0;
         }
 
         for (count, bin_value) in self
@@ -134,24 +730,49 @@ impl LongTerm {
         *self.latency_hist.last_mut().unwrap() += 1.0;
     }
 
-    fn success_rate(&self) -> f64 {
+    /// The number of successful responses. Unlike `failure_count`, this is not weighted.
+    fn success_count(&self) -> f64 {
+        let responses = self.latency_hist.iter().map(|c| *c as f64).sum::<f64>();
+        let failures = self.failure_kinds.iter().sum::<f64>();
+        (responses - failures).max(0.0)
+    }
+
+    /// The success rate, with the counts scaled by `retain` for pending decay.
+    fn success_rate(&self, retain: f64) -> f64 {
         // add 1 to pull success rate upward, and avoid divide by zero
-        let total = self.latency_hist.iter().map(|c| *c as f64).sum::<f64>() + 1.0;
-        let s = total - self.failure_count;
-        let f = self.failure_count;
+        let s = (self.success_count() * retain) + 1.0;
+        let f = self.failure_count * retain;
         s / (s + f)
     }
 
     pub fn latency_percentile(&self, p: u8) -> u16 {
-        debug_assert!((1..=99).contains(&p));
-        let target = self.latency_hist.iter().map(|c| *c as f64).sum::<f64>() * (p as f64 / 100.0);
-        let mut sum = 0.0;
-        for (count, bin_value) in self.latency_hist.iter().zip(&LATENCY_BINS) {
-            sum += *count as f64;
-            if sum >= target {
-                return *bin_value;
-            }
+        latency_percentile(self.latency_hist.map(|c| c as f64), p)
+    }
+
+    fn sample_success_rate<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        sample_success_rate(self.success_count(), self.failure_count, rng)
+    }
+
+    /// Sample the latency percentile from a histogram drawn from the Dirichlet posterior of the
+    /// bin counts.
+    fn sample_latency_percentile<R: Rng + ?Sized>(&self, p: u8, rng: &mut R) -> f64 {
+        let hist = self.latency_hist.map(|count| match count {
+            0.0 => 0.0,
+            _ => Gamma::new(count as f64, 1.0).unwrap().sample(rng),
+        });
+        latency_percentile(hist, p) as f64
+    }
+}
+
+fn latency_percentile(hist: [f64; 29], p: u8) -> u16 {
+    debug_assert!((1..=99).contains(&p));
+    let target = hist.iter().sum::<f64>() * (p as f64 / 100.0);
+    let mut sum = 0.0;
+    for (count, bin_value) in hist.iter().zip(&LATENCY_BINS) {
+        sum += *count;
+        if sum >= target {
+            return *bin_value;
         }
-        panic!("failed to calculate latency percentile");
     }
+    panic!("failed to calculate latency percentile");
 }
@@ -0,0 +1,569 @@
+use std::{f64::consts::E, ops::DerefMut};
+
+use candidate_selection::{ArrayVec, Normalized};
+
+use crate::Candidate;
+
+/// The parameters of the curves used to score each criterion, and of how the criteria are combined.
+///
+/// Deserialization fails for configs that are not valid. See [`ScoringConfig::validate`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "UnvalidatedScoringConfig")
+)]
+pub struct ScoringConfig {
+    pub seconds_behind: SecondsBehindCurve,
+    pub slashable_grt: SlashableGrtCurve,
+    pub latency: LatencyCurve,
+    pub success_rate: SuccessRateCurve,
+    pub weights: Weights,
+    /// The probability that all candidates in the same [group](Candidate::group) fail together,
+    /// such as when they share an operator or data center. At 0, failures of candidates are
+    /// independent. Higher values lower the combined success rate of candidates in the same group,
+    /// so that candidates in different groups are preferred for redundancy. A candidate with a lower
+    /// failure rate only fails with its group at its own failure rate. Must be in [0, 1).
+    pub group_failure_rate: f64,
+}
+
+impl ScoringConfig {
+    pub const DEFAULT: Self = Self {
+        seconds_behind: SecondsBehindCurve {
+            b: 1e-16,
+            l: 1.532,
+            k: 0.021,
+            x_0: 30.0,
+        },
+        // Currently setting a minimum score of ~0.8 at the minimum stake requirement of 100,000
+        // GRT.
+        slashable_grt: SlashableGrtCurve { a: 1.6e-5 },
+        latency: LatencyCurve {
+            midpoint_ms: 400.0,
+            scale_ms: 300.0,
+            floor: 0.001,
+        },
+        success_rate: SuccessRateCurve {
+            exponent: 7,
+            floor: 1e-8,
+        },
+        weights: Weights {
+            success_rate: 1.0,
+            latency: 1.0,
+            seconds_behind: 1.0,
+            slashable_grt: 1.0,
+        },
+        group_failure_rate: 0.0,
+    };
+
+    /// Check that each curve maps all of its inputs into `Normalized`, and that the weights keep
+    /// the combined score in `Normalized`.
+    pub fn validate(&self) -> Result<(), InvalidScoringConfig> {
+        if !self.seconds_behind.is_valid() {
+            return Err(InvalidScoringConfig::SecondsBehind);
+        }
+        if !self.slashable_grt.is_valid() {
+            return Err(InvalidScoringConfig::SlashableGrt);
+        }
+        if !self.latency.is_valid() {
+            return Err(InvalidScoringConfig::Latency);
+        }
+        if !self.success_rate.is_valid() {
+            return Err(InvalidScoringConfig::SuccessRate);
+        }
+        if !self.weights.is_valid() {
+            return Err(InvalidScoringConfig::Weights);
+        }
+        if !(0.0..1.0).contains(&self.group_failure_rate) {
+            return Err(InvalidScoringConfig::GroupFailureRate);
+        }
+        Ok(())
+    }
+
+    pub fn score_breakdown<I, D>(&self, candidate: &Candidate<I, D>) -> ScoreBreakdown {
+        ScoreBreakdown::new(
+            self.success_rate.score(candidate.perf.success_rate),
+            self.latency.score(candidate.perf.latency_ms),
+            self.seconds_behind.score(candidate.seconds_behind),
+            self.slashable_grt.score(candidate.slashable_grt),
+            &self.weights,
+        )
+    }
+
+    /// The breakdown of the combined score of a set of candidates, or `None` if their combined fee
+    /// exceeds the `budget`.
+    pub fn score_many_breakdown<I, D, const LIMIT: usize>(
+        &self,
+        candidates: &[&Candidate<I, D>],
+        budget: u128,
+    ) -> Option<ScoreBreakdown> {
+        self.score_many::<ArrayBuffers<LIMIT>, I, D>(candidates, budget)
+    }
+
+    /// Equivalent to `score_many_breakdown`, for sets of candidates with a size only known at
+    /// runtime.
+    pub fn score_many_breakdown_dyn<I, D>(
+        &self,
+        candidates: &[&Candidate<I, D>],
+        budget: u128,
+    ) -> Option<ScoreBreakdown> {
+        self.score_many::<VecBuffers, I, D>(candidates, budget)
+    }
+
+    /// An upper bound on the combined score of any set of candidates containing `candidates`.
+    pub(crate) fn score_bound<I, D>(&self, candidates: &[&Candidate<I, D>]) -> Normalized {
+        // Adding candidates can only improve the success rate & latency scores, but the combined
+        // seconds behind and slashable GRT are limited by the worst candidate.
+        let seconds_behind = candidates.iter().map(|c| c.seconds_behind).max().unwrap();
+        let slashable_grt = candidates.iter().map(|c| c.slashable_grt).min().unwrap();
+        self.weights
+            .seconds_behind(self.seconds_behind.score(seconds_behind))
+            * self
+                .weights
+                .slashable_grt(self.slashable_grt.score(slashable_grt))
+    }
+
+    fn score_many<B: Buffers, I, D>(
+        &self,
+        candidates: &[&Candidate<I, D>],
+        budget: u128,
+    ) -> Option<ScoreBreakdown> {
+        let fee = candidates
+            .iter()
+            .try_fold(0_u128, |sum, c| sum.checked_add(c.fee))?;
+        if fee > budget {
+            return None;
+        }
+
+        // candidate latencies
+        let ls: B::Buffer<u16> = candidates.iter().map(|c| c.perf.latency_ms).collect();
+        // probability of candidate responses returning to client, based on `ls`
+        let ps = {
+            let mut ps: B::Buffer<Normalized> =
+                candidates.iter().map(|c| c.perf.success_rate).collect();
+            let mut ls = ls.clone();
+            let mut sort = permutation::sort_unstable(&mut ls[..]);
+            sort.apply_slice_in_place(&mut ls[..]);
+            sort.apply_slice_in_place(&mut ps[..]);
+            if self.group_failure_rate > 0.0 {
+                let mut groups: B::Buffer<Option<u64>> =
+                    candidates.iter().map(|c| c.group).collect();
+                sort.apply_slice_in_place(&mut groups[..]);
+                let mut ps: B::Buffer<f64> = (0..ps.len())
+                    .map(|i| self.first_success_correlated(&ps, &groups, i))
+                    .collect();
+                sort.inverse().apply_slice_in_place(&mut ps[..]);
+                ps
+            } else {
+                let pf: B::Buffer<f64> = ps
+                    .iter()
+                    .map(|p| 1.0 - p.as_f64())
+                    .scan(1.0, |s, x| {
+                        *s *= x;
+                        Some(*s)
+                    })
+                    .collect();
+                let mut ps: B::Buffer<f64> = std::iter::once(&1.0)
+                    .chain(pf.iter())
+                    .zip(ps.iter())
+                    .map(|(&p, &s)| p * s.as_f64())
+                    .collect();
+                sort.inverse().apply_slice_in_place(&mut ps[..]);
+                ps
+            }
+        };
+
+        let success_rate = Normalized::new(ps.iter().sum()).unwrap_or(Normalized::ONE);
+        let latency = candidates
+            .iter()
+            .map(|c| c.perf.latency_ms as f64)
+            .zip(ps.iter())
+            .map(|(x, p)| x.recip() * p)
+            .sum::<f64>()
+            .recip() as u16;
+        let seconds_behind = candidates.iter().map(|c| c.seconds_behind).max().unwrap();
+        let slashable_grt = candidates.iter().map(|c| c.slashable_grt).min().unwrap();
+
+        Some(ScoreBreakdown::new(
+            self.success_rate.score(success_rate),
+            self.latency.score(latency),
+            self.seconds_behind.score(seconds_behind),
+            self.slashable_grt.score(slashable_grt),
+            &self.weights,
+        ))
+    }
+
+    /// The probability that the candidate at index `i` returns the first successful response, for
+    /// candidates sorted by latency. Each group of candidates fails together with probability
+    /// `group_failure_rate`, and otherwise its candidates fail independently. The group failures of
+    /// a candidate are capped at its own failure rate, so that its success rate is unchanged.
+    fn first_success_correlated(&self, ps: &[Normalized], groups: &[Option<u64>], i: usize) -> f64 {
+        // The rate at which candidate `j` fails with its group, and its success rate otherwise.
+        let rates = |j: usize| {
+            let p = ps[j].as_f64();
+            match groups[j] {
+                Some(_) => {
+                    let q = self.group_failure_rate.min(1.0 - p);
+                    (q, p / (1.0 - q))
+                }
+                None => (0.0, p),
+            }
+        };
+        // Candidate `i` must succeed, and all faster candidates must fail. For each candidate:
+        // (group failure rate, probability of this given a group failure, probability otherwise)
+        let outcome = |j: usize| {
+            let (q, p) = rates(j);
+            if j == i {
+                (q, 0.0, p)
+            } else {
+                (q, 1.0, 1.0 - p)
+            }
+        };
+        let mut p = 1.0;
+        for j in 0..=i {
+            match groups[j] {
+                None => p *= outcome(j).2,
+                Some(group) if groups[..j].contains(&Some(group)) => (),
+                Some(group) => {
+                    let members = (j..=i).filter(|&k| groups[k] == Some(group));
+                    p *= group_outcome(members.map(outcome));
+                }
+            }
+        }
+        p
+    }
+}
+
+/// The probability of an outcome for the candidates in a group, given as
+/// `(group failure rate, probability given a group failure, probability otherwise)` for each
+/// candidate. The group failures are nested: a group failure draws `u` uniformly from [0, 1), and
+/// fails each candidate with a group failure rate above `u`.
+fn group_outcome(members: impl Iterator<Item = (f64, f64, f64)> + Clone) -> f64 {
+    let bounds = members.clone().map(|(q, _, _)| q).chain([0.0]);
+    // sum over the intervals of `u` between consecutive distinct bounds
+    bounds
+        .clone()
+        .enumerate()
+        .filter(|&(n, from)| !bounds.clone().take(n).any(|q| q == from))
+        .map(|(_, from)| {
+            let to = bounds.clone().filter(|&q| q > from).fold(1.0, f64::min);
+            let p: f64 = members
+                .clone()
+                .map(|(q, failed, otherwise)| if from < q { failed } else { otherwise })
+                .product();
+            (to - from) * p
+        })
+        .sum()
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The fields of a [`ScoringConfig`], as deserialized before validation.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(default)]
+struct UnvalidatedScoringConfig {
+    seconds_behind: SecondsBehindCurve,
+    slashable_grt: SlashableGrtCurve,
+    latency: LatencyCurve,
+    success_rate: SuccessRateCurve,
+    weights: Weights,
+    group_failure_rate: f64,
+}
+
+#[cfg(feature = "serde")]
+impl Default for UnvalidatedScoringConfig {
+    fn default() -> Self {
+        let ScoringConfig {
+            seconds_behind,
+            slashable_grt,
+            latency,
+            success_rate,
+            weights,
+            group_failure_rate,
+        } = ScoringConfig::DEFAULT;
+        Self {
+            seconds_behind,
+            slashable_grt,
+            latency,
+            success_rate,
+            weights,
+            group_failure_rate,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UnvalidatedScoringConfig> for ScoringConfig {
+    type Error = InvalidScoringConfig;
+
+    fn try_from(config: UnvalidatedScoringConfig) -> Result<Self, Self::Error> {
+        let UnvalidatedScoringConfig {
+            seconds_behind,
+            slashable_grt,
+            latency,
+            success_rate,
+            weights,
+            group_failure_rate,
+        } = config;
+        let config = Self {
+            seconds_behind,
+            slashable_grt,
+            latency,
+            success_rate,
+            weights,
+            group_failure_rate,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidScoringConfig {
+    SecondsBehind,
+    SlashableGrt,
+    Latency,
+    SuccessRate,
+    Weights,
+    GroupFailureRate,
+}
+
+impl std::fmt::Display for InvalidScoringConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let curve = match self {
+            Self::SecondsBehind => "seconds_behind",
+            Self::SlashableGrt => "slashable_grt",
+            Self::Latency => "latency",
+            Self::SuccessRate => "success_rate",
+            Self::Weights => return write!(f, "weights must be finite and non-negative"),
+            Self::GroupFailureRate => return write!(f, "group_failure_rate must be in [0, 1)"),
+        };
+        write!(f, "{curve} curve does not map into [0, 1]")
+    }
+}
+
+impl std::error::Error for InvalidScoringConfig {}
+
+// When picking curves to use consider the following reference:
+// https://en.wikipedia.org/wiki/Logistic_function
+
+/// https://www.desmos.com/calculator/jdogbfxw2j
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SecondsBehindCurve {
+    pub b: f64,
+    pub l: f64,
+    pub k: f64,
+    pub x_0: f64,
+}
+
+impl SecondsBehindCurve {
+    pub fn score(&self, seconds_behind: u32) -> Normalized {
+        let Self { b, l, k, x_0 } = *self;
+        let u = b + (l / (1.0 + E.powf(k * (seconds_behind as f64 - x_0))));
+        Normalized::new(u).unwrap()
+    }
+
+    fn is_valid(&self) -> bool {
+        let Self { b, l, k, x_0 } = *self;
+        // The curve is decreasing in `seconds_behind`, so its maximum is at 0.
+        [b, l, k, x_0].iter().all(|x| x.is_finite())
+            && (b >= 0.0)
+            && (l >= 0.0)
+            && (k >= 0.0)
+            && ((b + (l / (1.0 + E.powf(-k * x_0)))) <= 1.0)
+    }
+}
+
+/// https://www.desmos.com/calculator/iqhjcdnphv
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlashableGrtCurve {
+    pub a: f64,
+}
+
+impl SlashableGrtCurve {
+    pub fn score(&self, slashable_grt: u64) -> Normalized {
+        let x = slashable_grt as f64;
+        Normalized::new(1.0 - E.powf(-self.a * x)).unwrap()
+    }
+
+    fn is_valid(&self) -> bool {
+        self.a.is_finite() && (self.a >= 0.0)
+    }
+}
+
+/// https://www.desmos.com/calculator/v2vrfktlpl
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LatencyCurve {
+    pub midpoint_ms: f64,
+    pub scale_ms: f64,
+    /// Since high latency becomes bad success rate via timeouts, latency scores should have a
+    /// floor.
+    pub floor: f64,
+}
+
+impl LatencyCurve {
+    pub fn score(&self, latency_ms: u16) -> Normalized {
+        let s = |x: u16| 1.0 + E.powf(((x as f64) - self.midpoint_ms) / self.scale_ms);
+        Normalized::clamp(s(0) / s(latency_ms), self.floor, 1.0).unwrap()
+    }
+
+    fn is_valid(&self) -> bool {
+        self.midpoint_ms.is_finite()
+            && self.scale_ms.is_finite()
+            && (self.scale_ms > 0.0)
+            && E.powf(-self.midpoint_ms / self.scale_ms).is_finite()
+            && (0.0..=1.0).contains(&self.floor)
+    }
+}
+
+/// https://www.desmos.com/calculator/df2keku3ad
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SuccessRateCurve {
+    pub exponent: i32,
+    pub floor: f64,
+}
+
+impl SuccessRateCurve {
+    pub fn score(&self, success_rate: Normalized) -> Normalized {
+        Normalized::clamp(success_rate.as_f64().powi(self.exponent), self.floor, 1.0).unwrap()
+    }
+
+    fn is_valid(&self) -> bool {
+        (self.exponent > 0) && (0.0..=1.0).contains(&self.floor)
+    }
+}
+
+/// The exponents applied to the score of each criterion in the
+/// [weighted product model](https://en.wikipedia.org/wiki/Weighted_product_model). A weight of 0
+/// ignores the criterion, and a weight greater than 1 makes the combined score more sensitive to it.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Weights {
+    pub success_rate: f64,
+    pub latency: f64,
+    pub seconds_behind: f64,
+    pub slashable_grt: f64,
+}
+
+impl Weights {
+    fn is_valid(&self) -> bool {
+        [
+            self.success_rate,
+            self.latency,
+            self.seconds_behind,
+            self.slashable_grt,
+        ]
+        .iter()
+        .all(|w| w.is_finite() && (*w >= 0.0))
+    }
+
+    fn success_rate(&self, score: Normalized) -> Normalized {
+        weigh(score, self.success_rate)
+    }
+    fn latency(&self, score: Normalized) -> Normalized {
+        weigh(score, self.latency)
+    }
+    fn seconds_behind(&self, score: Normalized) -> Normalized {
+        weigh(score, self.seconds_behind)
+    }
+    fn slashable_grt(&self, score: Normalized) -> Normalized {
+        weigh(score, self.slashable_grt)
+    }
+}
+
+fn weigh(score: Normalized, weight: f64) -> Normalized {
+    if weight == 1.0 {
+        return score;
+    }
+    Normalized::new(score.as_f64().powf(weight)).unwrap()
+}
+
+/// The scores for each criterion, as given by their curves. The combined score is the product of
+/// these scores, each raised to the power of its weight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScoreBreakdown {
+    pub success_rate: Normalized,
+    pub latency: Normalized,
+    pub seconds_behind: Normalized,
+    pub slashable_grt: Normalized,
+    pub combined: Normalized,
+}
+
+/// A criterion that candidates are scored on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Criterion {
+    SuccessRate,
+    Latency,
+    SecondsBehind,
+    SlashableGrt,
+}
+
+impl ScoreBreakdown {
+    /// The criterion with the lowest score, which is the one that a combined score of zero is
+    /// attributed to.
+    pub fn lowest(&self) -> Criterion {
+        [
+            (Criterion::SuccessRate, self.success_rate),
+            (Criterion::Latency, self.latency),
+            (Criterion::SecondsBehind, self.seconds_behind),
+            (Criterion::SlashableGrt, self.slashable_grt),
+        ]
+        .into_iter()
+        .min_by_key(|(_, score)| *score)
+        .unwrap()
+        .0
+    }
+
+    fn new(
+        success_rate: Normalized,
+        latency: Normalized,
+        seconds_behind: Normalized,
+        slashable_grt: Normalized,
+        weights: &Weights,
+    ) -> Self {
+        Self {
+            success_rate,
+            latency,
+            seconds_behind,
+            slashable_grt,
+            combined: [
+                weights.success_rate(success_rate),
+                weights.latency(latency),
+                weights.seconds_behind(seconds_behind),
+                weights.slashable_grt(slashable_grt),
+            ]
+            .into_iter()
+            .product(),
+        }
+    }
+}
+
+/// Storage for the intermediate values of `score_many`, so that the same code can be used for
+/// selections with a size known at compile time or at runtime.
+trait Buffers {
+    type Buffer<T: Clone>: Clone + FromIterator<T> + DerefMut<Target = [T]>;
+}
+
+struct ArrayBuffers<const LIMIT: usize>;
+impl<const LIMIT: usize> Buffers for ArrayBuffers<LIMIT> {
+    type Buffer<T: Clone> = ArrayVec<T, LIMIT>;
+}
+
+struct VecBuffers;
+impl Buffers for VecBuffers {
+    type Buffer<T: Clone> = Vec<T>;
+}
+
+/// https://www.desmos.com/calculator/v2vrfktlpl
+pub fn score_latency(latency_ms: u16) -> Normalized {
+    ScoringConfig::DEFAULT.latency.score(latency_ms)
+}
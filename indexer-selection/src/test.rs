@@ -1,7 +1,12 @@
-use std::ops::RangeInclusive;
+use std::{
+    f64::consts::E,
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
 
-use candidate_selection::{num::assert_within, Candidate as _};
-use proptest::{prop_assert, prop_compose, proptest};
+use candidate_selection::num::assert_within;
+use proptest::{prop_assert, prop_compose, proptest, strategy::Strategy as _};
+use rand::{rngs::StdRng, SeedableRng as _};
 
 use crate::*;
 
@@ -10,27 +15,156 @@ mod limits {
 
     #[test]
     fn success_rate() {
-        assert_within(score_success_rate(Normalized::ZERO).as_f64(), 1e-8, 0.001);
+        let curve = &ScoringConfig::DEFAULT.success_rate;
+        assert_within(curve.score(Normalized::ZERO).as_f64(), 1e-8, 0.001);
     }
 }
 
-prop_compose! {
-    fn normalized()(n in 0..=10) -> Normalized {
-        Normalized::new(n as f64 / 10.0).unwrap()
+mod scoring_config {
+    use super::*;
+
+    #[test]
+    fn validation() {
+        assert_eq!(Ok(()), ScoringConfig::default().validate());
+
+        let mut config = ScoringConfig::default();
+        config.seconds_behind.l = 2.0;
+        assert_eq!(Err(InvalidScoringConfig::SecondsBehind), config.validate());
+
+        let mut config = ScoringConfig::default();
+        config.slashable_grt.a = -1.0;
+        assert_eq!(Err(InvalidScoringConfig::SlashableGrt), config.validate());
+
+        let mut config = ScoringConfig::default();
+        config.latency.scale_ms = 0.0;
+        assert_eq!(Err(InvalidScoringConfig::Latency), config.validate());
+
+        let mut config = ScoringConfig::default();
+        config.success_rate.floor = f64::NAN;
+        assert_eq!(Err(InvalidScoringConfig::SuccessRate), config.validate());
+
+        let mut config = ScoringConfig::default();
+        config.weights.latency = -1.0;
+        assert_eq!(Err(InvalidScoringConfig::Weights), config.validate());
+
+        let config = ScoringConfig {
+            group_failure_rate: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(InvalidScoringConfig::GroupFailureRate),
+            config.validate()
+        );
+    }
+
+    #[test]
+    fn default_curves() {
+        // The curves as they were hard-coded before they were configurable.
+        let seconds_behind =
+            |x: u32| 1e-16 + (1.532 / (1.0 + E.powf(0.021 * (x as i64 - 30) as f64)));
+        let slashable_grt = |x: u64| 1.0 - E.powf(-1.6e-5 * x as f64);
+        let latency = |x: u16| {
+            let s = |x: u16| 1.0 + E.powf(((x as f64) - 400.0) / 300.0);
+            (s(0) / s(x)).clamp(0.001, 1.0)
+        };
+        let success_rate = |x: f64| x.powi(7).clamp(1e-8, 1.0);
+
+        let config = ScoringConfig::DEFAULT;
+        for x in [0, 30, 120, 3_600, 86_400] {
+            assert_eq!(seconds_behind(x), config.seconds_behind.score(x).as_f64());
+        }
+        for x in [0, 1_000, 100_000, 10_000_000] {
+            assert_eq!(slashable_grt(x), config.slashable_grt.score(x).as_f64());
+        }
+        for x in [0, 100, 400, 2_000, u16::MAX] {
+            assert_eq!(latency(x), config.latency.score(x).as_f64());
+        }
+        for x in [0.0, 0.05, 0.5, 0.99, 1.0] {
+            let score = config.success_rate.score(Normalized::new(x).unwrap());
+            assert_eq!(success_rate(x), score.as_f64());
+        }
+    }
+
+    #[test]
+    fn invalid_selection() {
+        let candidates = [test_candidate(0)];
+        let mut config = ScoringConfig::default();
+        config.latency.floor = 2.0;
+        let result: Result<ArrayVec<&Candidate<u64, ()>, 1>, _> =
+            crate::select_with_config(&candidates, BUDGET, &config);
+        assert!(matches!(
+            result,
+            Err(InvalidInput::InvalidConfig(InvalidScoringConfig::Latency))
+        ));
+        let result: Result<ConstrainedSelection<u64, (), 1>, _> =
+            crate::select_constrained(&candidates, BUDGET, &Constraints::default(), &config);
+        assert_eq!(Some(InvalidScoringConfig::Latency), result.err());
+        let result: Result<ConstrainedSelection<u64, (), 1>, _> =
+            crate::try_select(&candidates, BUDGET, &Constraints::default(), &config);
+        assert!(matches!(
+            result,
+            Err(SelectionError::InvalidConfig(InvalidScoringConfig::Latency))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let config: ScoringConfig =
+            serde_json::from_str(r#"{"seconds_behind": {"b": 0, "l": 1, "k": 0.1, "x_0": 10}}"#)
+                .unwrap();
+        assert_eq!(1.0, config.seconds_behind.l);
+        assert_eq!(ScoringConfig::DEFAULT.latency, config.latency);
+        assert_eq!(Ok(()), config.validate());
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(config, serde_json::from_str(&json).unwrap());
+
+        let invalid = r#"{"seconds_behind": {"b": 0, "l": 2, "k": 0.1, "x_0": 10}}"#;
+        let err = serde_json::from_str::<ScoringConfig>(invalid).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&InvalidScoringConfig::SecondsBehind.to_string()));
     }
 }
+
+const BUDGET: u128 = 1_000_000;
+
+/// A candidate that is caught up with the chain head, with a success rate of 0.99, a latency of
+/// 100 ms, and no fee. Tests override the fields they depend on with struct update syntax.
+fn test_candidate<I>(id: I) -> Candidate<I, ()> {
+    Candidate {
+        id,
+        data: (),
+        perf: perf(0.99, 100),
+        fee: 0,
+        seconds_behind: 0,
+        slashable_grt: 1_000_000,
+        group: None,
+    }
+}
+
+fn perf(success_rate: f64, latency_ms: u16) -> ExpectedPerformance {
+    ExpectedPerformance {
+        success_rate: Normalized::new(success_rate).unwrap(),
+        latency_ms,
+    }
+}
+
+fn score_many(candidates: &[&Candidate<u64, ()>]) -> Normalized {
+    Candidate::score_many_breakdown::<3>(candidates, BUDGET)
+        .map(|b| b.combined)
+        .unwrap_or(Normalized::ZERO)
+}
+
 prop_compose! {
     fn candidate()(
-        fee in normalized(),
-        versions_behind in 0..=3_u8,
+        fee in (0..=10_u128).prop_map(|n| n * BUDGET / 10),
         seconds_behind in 0..=7500_u16,
         slashable_grt: u32,
         avg_latency_ms: u16,
         avg_success_rate_percent in 0..=100_u8,
     ) -> Candidate<u64, ()> {
-        let mut deployment_bytes = [0; 32];
-        deployment_bytes[0] = versions_behind;
-
         let mut performance = Performance::default();
         for _ in 0..avg_success_rate_percent {
             performance.feedback(true, avg_latency_ms);
@@ -46,6 +180,7 @@ prop_compose! {
             fee,
             seconds_behind: seconds_behind as u32,
             slashable_grt: slashable_grt as u64,
+            group: None,
         }
     }
 }
@@ -64,7 +199,7 @@ proptest! {
     #[test]
     fn select(candidates in candidates(1..=5)) {
         println!("scores: {:#?}", candidates.iter().map(|c| (c.id, c.score())).collect::<Vec<_>>());
-        let selections: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates);
+        let selections: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates, BUDGET).unwrap();
         println!("selections: {:#?}", selections.iter().map(|c| c.id).collect::<Vec<_>>());
 
         let valid_candidate = |c: &Candidate<u64, ()>| -> bool {
@@ -79,6 +214,76 @@ proptest! {
             prop_assert!(selections.is_empty(), "no invalid candidate selected");
         }
     }
+
+    #[test]
+    fn unit_weights(candidates in candidates(1..=5)) {
+        let config = ScoringConfig {
+            weights: Weights {
+                success_rate: 1.0,
+                latency: 1.0,
+                seconds_behind: 1.0,
+                slashable_grt: 1.0,
+            },
+            ..ScoringConfig::default()
+        };
+        let unweighted = |b: ScoreBreakdown| -> Normalized {
+            [b.success_rate, b.latency, b.seconds_behind, b.slashable_grt].into_iter().product()
+        };
+        for candidate in &candidates {
+            let b = config.score_breakdown(candidate);
+            prop_assert!(b.combined == unweighted(b));
+        }
+        let candidates: Vec<&Candidate<u64, ()>> = candidates.iter().collect();
+        for n in 1..=candidates.len() {
+            // sets over the budget have no breakdown
+            if let Some(b) = config.score_many_breakdown::<_, _, 5>(&candidates[..n], BUDGET) {
+                prop_assert!(b.combined == unweighted(b));
+            }
+        }
+    }
+
+    #[test]
+    fn select_constrained(candidates in candidates(1..=5)) {
+        let constraints = Constraints {
+            max_seconds_behind: Some(3600),
+            min_slashable_grt: Some(100_000),
+            min_success_rate: Some(0.5),
+            max_latency_ms: Some(5_000),
+            max_fee: Some(BUDGET / 2),
+        };
+        let result: ConstrainedSelection<u64, (), 3> =
+            crate::select_constrained(&candidates, BUDGET, &constraints, &ScoringConfig::default()).unwrap();
+        prop_assert!(result.selected.iter().all(|c| constraints.check(c).is_ok()));
+        prop_assert!(result.rejected.iter().all(|r| constraints.check(r.candidate) == Err(r.constraint)));
+        prop_assert!(result.selected.iter().all(|c| result.rejected.iter().all(|r| r.candidate.id != c.id)));
+
+        let expected: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates, BUDGET).unwrap();
+        let result: ConstrainedSelection<u64, (), 3> =
+            crate::select_constrained(&candidates, BUDGET, &Constraints::default(), &ScoringConfig::default()).unwrap();
+        prop_assert!(result.rejected.is_empty());
+        prop_assert!(expected.iter().map(|c| c.id).eq(result.selected.iter().map(|c| c.id)));
+    }
+
+    #[test]
+    fn select_dyn(candidates in candidates(1..=5)) {
+        let expected: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates, BUDGET).unwrap();
+        let selections = crate::select_dyn(&candidates, BUDGET, 3).unwrap();
+        prop_assert!(expected.iter().map(|c| c.id).eq(selections.iter().map(|c| c.id)));
+        prop_assert!(score_many(&expected) == Candidate::score_many_breakdown_dyn(&selections, BUDGET).map(|b| b.combined).unwrap_or(Normalized::ZERO));
+    }
+
+    #[test]
+    fn select_optimal(candidates in candidates(1..=8)) {
+        let greedy: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates, BUDGET).unwrap();
+        let greedy_score = match greedy.len() {
+            0 => Normalized::ZERO,
+            1 => greedy[0].score(),
+            _ => score_many(&greedy),
+        };
+        let optimal = crate::select_optimal::<_, _, 3>(&candidates, BUDGET, SearchLimits::default()).unwrap();
+        prop_assert!(optimal.complete);
+        prop_assert!(greedy_score <= optimal.score, "optimal is at least as good as greedy");
+    }
 }
 
 #[test]
@@ -91,9 +296,10 @@ fn sensitivity_seconds_behind() {
                 success_rate: Normalized::new(0.99).unwrap(),
                 latency_ms: 0,
             },
-            fee: Normalized::ZERO,
+            fee: 0,
             seconds_behind: 86400,
             slashable_grt: 1_000_000,
+            group: None,
         },
         Candidate {
             id: 1,
@@ -102,9 +308,10 @@ fn sensitivity_seconds_behind() {
                 success_rate: Normalized::new(0.5).unwrap(),
                 latency_ms: 1000,
             },
-            fee: Normalized::ONE,
+            fee: BUDGET,
             seconds_behind: 120,
             slashable_grt: 100_000,
+            group: None,
         },
     ];
 
@@ -112,7 +319,7 @@ fn sensitivity_seconds_behind() {
     println!("score {} {:?}", candidates[1].id, candidates[1].score(),);
     assert!(candidates[0].score() <= candidates[1].score());
 
-    let selections: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates);
+    let selections: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates, BUDGET).unwrap();
     assert_eq!(1, selections.len(), "select exactly one candidate");
     assert_eq!(
         Some(candidates[1].id),
@@ -121,6 +328,382 @@ fn sensitivity_seconds_behind() {
     );
 }
 
+#[test]
+fn scoring_config_seconds_behind() {
+    let candidates = [
+        Candidate {
+            id: 0,
+            data: (),
+            perf: ExpectedPerformance {
+                success_rate: Normalized::new(0.99).unwrap(),
+                latency_ms: 50,
+            },
+            fee: 0,
+            seconds_behind: 120,
+            slashable_grt: 1_000_000,
+            group: None,
+        },
+        Candidate {
+            id: 1,
+            data: (),
+            perf: ExpectedPerformance {
+                success_rate: Normalized::new(0.99).unwrap(),
+                latency_ms: 1000,
+            },
+            fee: 0,
+            seconds_behind: 0,
+            slashable_grt: 1_000_000,
+            group: None,
+        },
+    ];
+
+    let selections: ArrayVec<&Candidate<u64, ()>, 1> = crate::select(&candidates, BUDGET).unwrap();
+    assert_eq!(Some(0), selections.first().map(|s| s.id));
+
+    // a chain where falling behind by a couple of blocks is not acceptable
+    let mut config = ScoringConfig::default();
+    config.seconds_behind.x_0 = 10.0;
+    config.seconds_behind.k = 0.1;
+    config.seconds_behind.l = 1.3;
+    config.validate().unwrap();
+    let selections: ArrayVec<&Candidate<u64, ()>, 1> =
+        crate::select_with_config(&candidates, BUDGET, &config).unwrap();
+    assert_eq!(Some(1), selections.first().map(|s| s.id));
+}
+
+#[test]
+fn constraint_rejections() {
+    let candidates = [
+        test_candidate(0),
+        Candidate {
+            seconds_behind: 600,
+            ..test_candidate(1)
+        },
+        Candidate {
+            slashable_grt: 10,
+            ..test_candidate(2)
+        },
+        Candidate {
+            perf: perf(0.2, 100),
+            ..test_candidate(3)
+        },
+        Candidate {
+            perf: perf(0.99, 9_000),
+            ..test_candidate(4)
+        },
+        Candidate {
+            fee: BUDGET * 9 / 10,
+            ..test_candidate(5)
+        },
+    ];
+    let constraints = Constraints {
+        max_seconds_behind: Some(60),
+        min_slashable_grt: Some(100_000),
+        min_success_rate: Some(0.9),
+        max_latency_ms: Some(1_000),
+        max_fee: Some(BUDGET / 2),
+    };
+    let result: ConstrainedSelection<u64, (), 3> =
+        crate::select_constrained(&candidates, BUDGET, &constraints, &ScoringConfig::default())
+            .unwrap();
+    assert_eq!(
+        vec![0],
+        result.selected.iter().map(|c| c.id).collect::<Vec<_>>()
+    );
+    let rejected: Vec<(u64, Constraint)> = result
+        .rejected
+        .iter()
+        .map(|r| (r.candidate.id, r.constraint))
+        .collect();
+    assert_eq!(
+        vec![
+            (1, Constraint::MaxSecondsBehind),
+            (2, Constraint::MinSlashableGrt),
+            (3, Constraint::MinSuccessRate),
+            (4, Constraint::MaxLatency),
+            (5, Constraint::MaxFee),
+        ],
+        rejected
+    );
+}
+
+#[test]
+fn fee_budget() {
+    let candidates = [
+        Candidate {
+            fee: 400,
+            ..test_candidate(0)
+        },
+        Candidate {
+            fee: 300,
+            ..test_candidate(1)
+        },
+        Candidate {
+            fee: 300,
+            ..test_candidate(2)
+        },
+        Candidate {
+            fee: 200,
+            ..test_candidate(3)
+        },
+        Candidate {
+            fee: 1_001,
+            ..test_candidate(4)
+        },
+    ];
+    let selected: ArrayVec<&Candidate<u64, ()>, 5> = crate::select(&candidates, 1_000).unwrap();
+    let total: u128 = selected.iter().map(|c| c.fee).sum();
+    assert!(total <= 1_000);
+    assert!(selected.len() >= 3);
+    assert!(selected.iter().all(|c| c.id != 4));
+
+    let selected: ArrayVec<&Candidate<u64, ()>, 5> = crate::select(&candidates, 0).unwrap();
+    assert!(selected.is_empty());
+
+    // The fractions of the budget sum to more than 1 as floats.
+    let exact = [
+        Candidate {
+            fee: 197,
+            ..test_candidate(0)
+        },
+        Candidate {
+            fee: 687,
+            ..test_candidate(1)
+        },
+        Candidate {
+            fee: 116,
+            ..test_candidate(2)
+        },
+    ];
+    let selected: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&exact, 1_000).unwrap();
+    assert_eq!(3, selected.len());
+    let optimal = crate::select_optimal::<_, _, 3>(&exact, 1_000, SearchLimits::default()).unwrap();
+    assert_eq!(3, optimal.selected.len());
+    assert_eq!(3, crate::select_dyn(&exact, 1_000, 3).unwrap().len());
+    // The budget given to the selection replaces the one in the options.
+    let options = SelectionOptions {
+        fee_budget: Some(0),
+        ..SelectionOptions::DEFAULT
+    };
+    let selected: ArrayVec<&Candidate<u64, ()>, 3> =
+        crate::select_with_options(&exact, 1_000, &ScoringConfig::DEFAULT, &options).unwrap();
+    assert_eq!(3, selected.len());
+
+    let result = crate::try_select::<_, _, 3>(
+        &candidates[4..],
+        1_000,
+        &Constraints::default(),
+        &ScoringConfig::default(),
+    );
+    assert_eq!(
+        "all candidates rejected by constraints (1: fee over budget)",
+        result.unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn selection_errors() {
+    let constraints = Constraints {
+        max_seconds_behind: Some(60),
+        ..Default::default()
+    };
+    let config = ScoringConfig::default();
+
+    let result = crate::try_select::<u64, (), 3>(&[], BUDGET, &constraints, &config);
+    assert!(matches!(result, Err(SelectionError::NoCandidates)));
+
+    let candidates = [
+        test_candidate(0),
+        Candidate {
+            seconds_behind: 600,
+            ..test_candidate(1)
+        },
+    ];
+    let result = crate::try_select::<_, _, 3>(&candidates, BUDGET, &constraints, &config).unwrap();
+    assert_eq!(1, result.selected.len());
+    assert_eq!(1, result.rejected.len());
+
+    let candidates = [
+        Candidate {
+            seconds_behind: 600,
+            ..test_candidate(0)
+        },
+        Candidate {
+            seconds_behind: 600,
+            ..test_candidate(1)
+        },
+    ];
+    let result = crate::try_select::<_, _, 3>(&candidates, BUDGET, &constraints, &config);
+    let err = result.unwrap_err();
+    assert_eq!(
+        "all candidates rejected by constraints (2: too far behind chain head)",
+        err.to_string()
+    );
+    assert!(matches!(err, SelectionError::AllRejected(r) if r.len() == 2));
+
+    let candidates = [
+        Candidate {
+            slashable_grt: 0,
+            ..test_candidate(0)
+        },
+        Candidate {
+            seconds_behind: 600,
+            ..test_candidate(1)
+        },
+    ];
+    let result = crate::try_select::<_, _, 3>(&candidates, BUDGET, &constraints, &config);
+    let err = result.unwrap_err();
+    assert_eq!(
+        "no candidates with a score above zero (1: slashable GRT, 1 rejected by constraints)",
+        err.to_string()
+    );
+    match err {
+        SelectionError::AllScoredZero { zero, rejected } => {
+            assert_eq!(1, rejected.len());
+            assert_eq!(1, zero.len());
+            assert_eq!(0, zero[0].candidate.id);
+            assert_eq!(Criterion::SlashableGrt, zero[0].criterion);
+        }
+        _ => panic!("unexpected error: {err:?}"),
+    };
+
+    let candidates = [
+        test_candidate(0),
+        test_candidate(1),
+        Candidate {
+            seconds_behind: 10,
+            ..test_candidate(0)
+        },
+    ];
+    let result: ConstrainedSelection<u64, (), 3> =
+        crate::select_constrained(&candidates, BUDGET, &constraints, &config).unwrap();
+    assert_eq!(2, result.selected.len());
+    assert_eq!(1, result.rejected.len());
+    assert_eq!(Constraint::DuplicateId, result.rejected[0].constraint);
+    assert_eq!(10, result.rejected[0].candidate.seconds_behind);
+    let result = crate::try_select::<_, _, 3>(&candidates, BUDGET, &constraints, &config);
+    let err = result.unwrap_err();
+    assert_eq!("duplicate candidate IDs (1)", err.to_string());
+}
+
+#[test]
+fn candidate_ids() {
+    let candidates = [test_candidate("a"), test_candidate("b")];
+    let explained = crate::select_explained::<_, _, 3>(&candidates, BUDGET).unwrap();
+    assert_eq!(Some("b"), explained.rounds[0].selected);
+    assert_eq!(
+        vec!["a", "b"],
+        explained.rounds[0]
+            .evaluations
+            .iter()
+            .map(|e| e.id)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(2, explained.selected.len());
+
+    // Every selection function reports duplicate IDs, other than `select_constrained`, which
+    // rejects them.
+    let candidates = [
+        test_candidate("a"),
+        test_candidate("b"),
+        test_candidate("a"),
+        test_candidate("a"),
+    ];
+    let duplicates =
+        |err: DuplicateIds<&'static str, ()>| -> Vec<*const Candidate<&'static str, ()>> {
+            err.0.into_iter().map(|c| c as *const _).collect()
+        };
+    let expected: Vec<*const Candidate<&'static str, ()>> = vec![&candidates[2], &candidates[3]];
+    let config = ScoringConfig::DEFAULT;
+    let performance = Performance::default();
+    let mut rng = StdRng::seed_from_u64(0);
+    let results = [
+        crate::select::<_, _, 3>(&candidates, BUDGET)
+            .map(drop)
+            .map_err(InvalidInput::from),
+        crate::select_with_config::<_, _, 3>(&candidates, BUDGET, &config).map(drop),
+        crate::select_thompson::<_, _, _, 3>(
+            &candidates,
+            BUDGET,
+            &config,
+            |_| &performance,
+            &mut rng,
+        )
+        .map(drop),
+        crate::select_exploring::<_, _, _, 3>(
+            &candidates,
+            BUDGET,
+            &config,
+            &Exploration::default(),
+            |_| &performance,
+            &mut rng,
+        )
+        .map(drop),
+        crate::select_with_options::<_, _, 3>(
+            &candidates,
+            BUDGET,
+            &config,
+            &SelectionOptions::default(),
+        )
+        .map(drop),
+        crate::select_dyn(&candidates, BUDGET, 3)
+            .map(drop)
+            .map_err(InvalidInput::from),
+        crate::select_explained::<_, _, 3>(&candidates, BUDGET)
+            .map(drop)
+            .map_err(InvalidInput::from),
+        crate::select_optimal::<_, _, 3>(&candidates, BUDGET, SearchLimits::default())
+            .map(drop)
+            .map_err(InvalidInput::from),
+    ];
+    for result in results {
+        let Err(InvalidInput::DuplicateIds(err)) = result else {
+            panic!("expected duplicate IDs");
+        };
+        assert_eq!(expected, duplicates(err));
+    }
+}
+
+#[test]
+fn latency_weight() {
+    let candidates = [
+        Candidate {
+            id: 0,
+            data: (),
+            perf: ExpectedPerformance {
+                success_rate: Normalized::new(0.99).unwrap(),
+                latency_ms: 600,
+            },
+            fee: 0,
+            seconds_behind: 0,
+            slashable_grt: 1_000_000,
+            group: None,
+        },
+        Candidate {
+            id: 1,
+            data: (),
+            perf: ExpectedPerformance {
+                success_rate: Normalized::new(0.99).unwrap(),
+                latency_ms: 300,
+            },
+            fee: 0,
+            seconds_behind: 60,
+            slashable_grt: 1_000_000,
+            group: None,
+        },
+    ];
+
+    let selections: ArrayVec<&Candidate<u64, ()>, 1> = crate::select(&candidates, BUDGET).unwrap();
+    assert_eq!(Some(0), selections.first().map(|s| s.id));
+
+    let mut config = ScoringConfig::default();
+    config.weights.latency = 3.0;
+    config.validate().unwrap();
+    let selections: ArrayVec<&Candidate<u64, ()>, 1> =
+        crate::select_with_config(&candidates, BUDGET, &config).unwrap();
+    assert_eq!(Some(1), selections.first().map(|s| s.id));
+}
+
 #[test]
 fn sensitivity_seconds_behind_vs_latency() {
     let candidates = [
@@ -131,9 +714,10 @@ fn sensitivity_seconds_behind_vs_latency() {
                 success_rate: Normalized::new(0.99).unwrap(),
                 latency_ms: 0,
             },
-            fee: Normalized::ZERO,
+            fee: 0,
             seconds_behind: 35_000_000,
             slashable_grt: 1_600_000,
+            group: None,
         },
         Candidate {
             id: 1,
@@ -142,9 +726,10 @@ fn sensitivity_seconds_behind_vs_latency() {
                 success_rate: Normalized::new(0.99).unwrap(),
                 latency_ms: 10_000,
             },
-            fee: Normalized::ZERO,
+            fee: 0,
             seconds_behind: 120,
             slashable_grt: 100_000,
+            group: None,
         },
     ];
 
@@ -152,7 +737,7 @@ fn sensitivity_seconds_behind_vs_latency() {
     println!("score {} {:?}", candidates[1].id, candidates[1].score(),);
     assert!(candidates[0].score() <= candidates[1].score());
 
-    let selections: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates);
+    let selections: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates, BUDGET).unwrap();
     assert_eq!(1, selections.len(), "select exactly one candidate");
     assert_eq!(
         Some(candidates[1].id),
@@ -171,9 +756,10 @@ fn multi_selection_preference() {
                 success_rate: Normalized::new(0.99).unwrap(),
                 latency_ms: 93,
             },
-            fee: Normalized::ZERO,
+            fee: 0,
             seconds_behind: 0,
             slashable_grt: 9445169,
+            group: None,
         },
         Candidate {
             id: 1,
@@ -182,9 +768,10 @@ fn multi_selection_preference() {
                 success_rate: Normalized::new(0.99).unwrap(),
                 latency_ms: 0,
             },
-            fee: Normalized::ZERO,
+            fee: 0,
             seconds_behind: 0,
             slashable_grt: 1330801,
+            group: None,
         },
         Candidate {
             id: 2,
@@ -193,24 +780,21 @@ fn multi_selection_preference() {
                 success_rate: Normalized::new(0.99).unwrap(),
                 latency_ms: 224,
             },
-            fee: Normalized::ZERO,
+            fee: 0,
             seconds_behind: 0,
             slashable_grt: 2675210,
+            group: None,
         },
     ];
 
-    for c in &candidates {
-        println!("{} {:?}", c.id, c.score());
-    }
-    let combined_score = Candidate::score_many::<3>(
+    let combined_score = score_many(
         &candidates
             .iter()
             .collect::<ArrayVec<&Candidate<u64, ()>, 3>>(),
     );
     assert!(candidates.iter().all(|c| c.score() < combined_score));
 
-    let selected: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates);
-    println!("{:#?}", selected);
+    let selected: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates, BUDGET).unwrap();
     assert_eq!(3, selected.len(), "all indexers selected");
 }
 
@@ -224,9 +808,10 @@ fn low_volume_response() {
                 success_rate: Normalized::new(0.99).unwrap(),
                 latency_ms: 0,
             },
-            fee: Normalized::ZERO,
+            fee: 0,
             seconds_behind: 0,
             slashable_grt: 100000,
+            group: None,
         },
         Candidate {
             id: 1,
@@ -235,9 +820,10 @@ fn low_volume_response() {
                 success_rate: Normalized::new(0.99).unwrap(),
                 latency_ms: 0,
             },
-            fee: Normalized::ZERO,
+            fee: 0,
             seconds_behind: 0,
             slashable_grt: 100000,
+            group: None,
         },
         Candidate {
             id: 2,
@@ -246,24 +832,21 @@ fn low_volume_response() {
                 success_rate: Normalized::new(0.99).unwrap(),
                 latency_ms: 0,
             },
-            fee: Normalized::ZERO,
+            fee: 0,
             seconds_behind: 0,
             slashable_grt: 100000,
+            group: None,
         },
     ];
 
-    for c in &candidates {
-        println!("{} {:?}", c.id, c.score());
-    }
-    let combined_score = Candidate::score_many::<3>(
+    let combined_score = score_many(
         &candidates
             .iter()
             .collect::<ArrayVec<&Candidate<u64, ()>, 3>>(),
     );
     assert!(candidates.iter().all(|c| c.score() < combined_score));
 
-    let selected: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates);
-    println!("{:#?}", selected);
+    let selected: ArrayVec<&Candidate<u64, ()>, 3> = crate::select(&candidates, BUDGET).unwrap();
     assert_eq!(3, selected.len(), "all indexers selected");
 }
 
@@ -274,9 +857,10 @@ fn perf_decay() {
         id: 0,
         data: (),
         perf: perf.expected_performance(),
-        fee: Normalized::ZERO,
+        fee: 0,
         seconds_behind: 0,
         slashable_grt: 1_000_000,
+        group: None,
     };
 
     let mut simulate = |seconds, success, latency_ms| {
@@ -285,7 +869,7 @@ fn perf_decay() {
             for _ in 0..feedback_hz {
                 perf.feedback(success, latency_ms);
             }
-            perf.decay();
+            perf.decay(Duration::from_secs(1));
         }
         candidate.perf = perf.expected_performance();
         candidate.score()
@@ -296,8 +880,717 @@ fn perf_decay() {
     let s2 = simulate(8, false, 10).as_f64();
     let s3 = simulate(120, true, 200).as_f64();
 
-    println!("{s0:.4} {s1:.4} {s2:.4} {s3:.4}");
     assert!(s1 < (s0 * 0.8));
     assert!(s2 < (s0 * 0.1));
     assert!(s3 > (s0 * 0.5));
 }
+
+#[test]
+fn thompson_sampling() {
+    let perf = |responses, success_rate| {
+        let mut perf = Performance::default();
+        for i in 0..responses {
+            perf.feedback((i % 100) < success_rate, 200);
+        }
+        perf
+    };
+    let performances = [perf(30_000, 95), perf(3, 100)];
+    let candidates: Vec<Candidate<u64, ()>> = performances
+        .iter()
+        .enumerate()
+        .map(|(id, perf)| Candidate {
+            id: id as u64,
+            data: (),
+            perf: perf.expected_performance(),
+            fee: 0,
+            seconds_behind: 0,
+            slashable_grt: 1_000_000,
+            group: None,
+        })
+        .collect();
+
+    // The success rates sampled for the candidate with few responses are more spread out.
+    let mut rng = StdRng::seed_from_u64(0);
+    let spread = |perf: &Performance, rng: &mut StdRng| {
+        let samples: Vec<f64> = (0..1_000)
+            .map(|_| perf.sample_performance(rng).success_rate.as_f64())
+            .collect();
+        let min = samples.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = samples.iter().copied().fold(0.0, f64::max);
+        max - min
+    };
+    let spreads = [
+        spread(&performances[0], &mut rng),
+        spread(&performances[1], &mut rng),
+    ];
+    assert!(spreads[0] < 0.05);
+    assert!(spreads[1] > 0.3);
+
+    let select = |seed| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let selected: ArrayVec<&Candidate<u64, ()>, 1> = crate::select_thompson(
+            &candidates,
+            BUDGET,
+            &ScoringConfig::DEFAULT,
+            |c| &performances[c.id as usize],
+            &mut rng,
+        )
+        .unwrap();
+        selected[0].id
+    };
+    let expected: ArrayVec<&Candidate<u64, ()>, 1> = crate::select(&candidates, BUDGET).unwrap();
+    assert_eq!(1, expected[0].id);
+    let selections: Vec<u64> = (0..100).map(select).collect();
+    assert!(selections.contains(&0) && selections.contains(&1));
+    assert_eq!(selections, (0..100).map(select).collect::<Vec<u64>>());
+}
+
+#[test]
+fn exploration() {
+    let mut measured = Performance::default();
+    for _ in 0..1_000 {
+        measured.feedback(true, 100);
+    }
+    let performances = [measured, Performance::default()];
+    assert_eq!(1_000.0, performances[0].sample_count());
+    assert_eq!(0.0, performances[1].sample_count());
+    // The unmeasured candidate would not be selected for its score.
+    let candidates = [
+        Candidate {
+            fee: 600,
+            ..test_candidate(0)
+        },
+        Candidate {
+            perf: perf(0.0, 100),
+            fee: 400,
+            ..test_candidate(1)
+        },
+    ];
+    let expected: ArrayVec<&Candidate<u64, ()>, 2> = crate::select(&candidates, 1_000).unwrap();
+    assert_eq!(1, expected.len());
+
+    let explorations = |epsilon, budget| {
+        let exploration = Exploration {
+            epsilon,
+            min_samples: 100.0,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..1_000)
+            .filter(|_| {
+                let selected: ArrayVec<&Candidate<u64, ()>, 2> = crate::select_exploring(
+                    &candidates,
+                    budget,
+                    &ScoringConfig::DEFAULT,
+                    &exploration,
+                    |c| &performances[c.id as usize],
+                    &mut rng,
+                )
+                .unwrap();
+                selected.len() == 2
+            })
+            .count()
+    };
+    assert_eq!(0, explorations(0.0, 1_000));
+    assert_eq!(1_000, explorations(1.0, 1_000));
+    assert!((150..250).contains(&explorations(0.2, 1_000)));
+    assert_eq!(0, explorations(1.0, 999));
+    assert_eq!(0, explorations(f64::NAN, 1_000));
+    assert_eq!(0, explorations(f64::INFINITY, 1_000));
+}
+
+#[test]
+fn correlated_failures() {
+    let candidates = [
+        Candidate {
+            perf: perf(0.9, 100),
+            group: Some(1),
+            ..test_candidate(0)
+        },
+        Candidate {
+            perf: perf(0.9, 101),
+            group: Some(1),
+            ..test_candidate(1)
+        },
+        Candidate {
+            perf: perf(0.9, 102),
+            ..test_candidate(2)
+        },
+    ];
+    let success_rate =
+        |config: &ScoringConfig, candidates: &[Candidate<u64, ()>], ids: &[usize]| {
+            let set: Vec<&Candidate<u64, ()>> = ids.iter().map(|&i| &candidates[i]).collect();
+            config
+                .score_many_breakdown_dyn(&set, BUDGET)
+                .unwrap()
+                .success_rate
+                .as_f64()
+        };
+
+    let independent = ScoringConfig::DEFAULT;
+    assert_eq!(
+        success_rate(&independent, &candidates, &[0, 1]),
+        success_rate(&independent, &candidates, &[0, 2])
+    );
+
+    let correlated = ScoringConfig {
+        group_failure_rate: 0.05,
+        ..ScoringConfig::DEFAULT
+    };
+    assert_eq!(Ok(()), correlated.validate());
+    // Without a shared group, failures are still independent.
+    assert_within(
+        success_rate(&correlated, &candidates, &[0, 2]),
+        success_rate(&independent, &candidates, &[0, 2]),
+        1e-9,
+    );
+    // A group fails with probability 0.05, and otherwise each candidate succeeds with probability
+    // 0.9 / 0.95.
+    let p: f64 = 0.9 / 0.95;
+    assert_within(
+        success_rate(&correlated, &candidates, &[0, 1]),
+        ScoringConfig::DEFAULT
+            .success_rate
+            .score(Normalized::new(0.95 * (1.0 - (1.0 - p).powi(2))).unwrap())
+            .as_f64(),
+        1e-9,
+    );
+    assert!(
+        success_rate(&correlated, &candidates, &[0, 1])
+            < success_rate(&correlated, &candidates, &[0, 2])
+    );
+
+    let selected: ArrayVec<&Candidate<u64, ()>, 2> =
+        crate::select_with_config(&candidates, BUDGET, &correlated).unwrap();
+    assert_eq!(2, selected.len());
+    assert!(selected.iter().any(|c| c.id == 2));
+
+    // Candidates more reliable than their groups keep their success rates, so groups of one
+    // candidate are equivalent to independent failures.
+    let reliable = [
+        Candidate {
+            group: Some(1),
+            ..test_candidate(0)
+        },
+        Candidate {
+            perf: perf(0.99, 101),
+            group: Some(2),
+            ..test_candidate(1)
+        },
+        Candidate {
+            perf: perf(0.99, 102),
+            ..test_candidate(2)
+        },
+        Candidate {
+            perf: perf(0.99, 103),
+            group: Some(1),
+            ..test_candidate(3)
+        },
+    ];
+    for ids in [&[0][..], &[0, 2], &[0, 1], &[1, 0, 2]] {
+        assert_within(
+            success_rate(&correlated, &reliable, ids),
+            success_rate(&independent, &reliable, ids),
+            1e-9,
+        );
+    }
+    assert!(
+        success_rate(&correlated, &reliable, &[0, 3])
+            < success_rate(&correlated, &reliable, &[0, 1])
+    );
+}
+
+#[test]
+fn selection_options() {
+    let candidates = [
+        Candidate {
+            perf: perf(0.99, 100),
+            fee: BUDGET / 10,
+            group: Some(1),
+            ..test_candidate(0)
+        },
+        Candidate {
+            perf: perf(0.98, 100),
+            fee: BUDGET / 10,
+            group: Some(1),
+            ..test_candidate(1)
+        },
+        Candidate {
+            perf: perf(0.9, 100),
+            fee: BUDGET / 10,
+            group: Some(2),
+            ..test_candidate(2)
+        },
+        Candidate {
+            perf: perf(0.5, 100),
+            fee: BUDGET / 10,
+            ..test_candidate(3)
+        },
+    ];
+    let config = ScoringConfig::DEFAULT;
+    let select = |options: &SelectionOptions<u64>| -> Vec<u64> {
+        let selected: ArrayVec<&Candidate<u64, ()>, 2> =
+            crate::select_with_options(&candidates, BUDGET, &config, options).unwrap();
+        selected.iter().map(|c| c.id).collect()
+    };
+
+    let default: ArrayVec<&Candidate<u64, ()>, 2> =
+        crate::select_with_config(&candidates, BUDGET, &config).unwrap();
+    let default: Vec<u64> = default.iter().map(|c| c.id).collect();
+    assert_eq!(default, select(&SelectionOptions::DEFAULT));
+    assert_eq!(vec![0, 1], default);
+
+    let excluded = select(&SelectionOptions {
+        excluded: [0].into(),
+        ..Default::default()
+    });
+    assert_eq!(vec![1, 2], excluded);
+
+    let pinned = select(&SelectionOptions {
+        pinned: [3].into(),
+        ..Default::default()
+    });
+    assert_eq!(vec![3, 0], pinned);
+
+    let grouped = select(&SelectionOptions {
+        max_per_group: Some(1),
+        ..Default::default()
+    });
+    assert_eq!(vec![0, 2], grouped);
+
+    // The unreliable candidate only lowers the combined score, so it is only selected to meet the
+    // minimum.
+    let unreliable = [
+        Candidate {
+            perf: perf(0.99, 100),
+            fee: BUDGET / 10,
+            ..test_candidate(0)
+        },
+        Candidate {
+            perf: perf(0.001, 100),
+            fee: BUDGET / 10,
+            ..test_candidate(1)
+        },
+    ];
+    let minimum = |min_selected| -> Vec<u64> {
+        let options = SelectionOptions {
+            min_selected,
+            ..Default::default()
+        };
+        let selected: ArrayVec<&Candidate<u64, ()>, 2> =
+            crate::select_with_options(&unreliable, BUDGET, &config, &options).unwrap();
+        selected.iter().map(|c| c.id).collect()
+    };
+    assert_eq!(vec![0], minimum(0));
+    assert_eq!(vec![0, 1], minimum(2));
+
+    let invalid = SelectionOptions {
+        price_sensitivity: f64::NAN,
+        ..Default::default()
+    };
+    let result: Result<ArrayVec<&Candidate<u64, ()>, 2>, _> =
+        crate::select_with_options(&candidates, BUDGET, &config, &invalid);
+    assert!(matches!(
+        result,
+        Err(InvalidInput::InvalidOptions(
+            InvalidSelectionOptions::PriceSensitivity
+        ))
+    ));
+}
+
+#[test]
+fn perf_decay_elapsed() {
+    let mut ticks = Performance::default();
+    let mut elapsed = Performance::default();
+    for _ in 0..100 {
+        ticks.feedback(false, 100);
+        elapsed.feedback(false, 100);
+    }
+    for _ in 0..30 {
+        ticks.decay(Duration::from_secs(1));
+    }
+    elapsed.decay(Duration::from_secs(30));
+    let ticks = ticks.expected_performance().success_rate.as_f64();
+    assert_within(
+        ticks,
+        elapsed.expected_performance().success_rate.as_f64(),
+        1e-6,
+    );
+
+    // lazy decay
+    let start = Instant::now();
+    let mut lazy = Performance::default();
+    for _ in 0..100 {
+        lazy.feedback_at(start, false, 100);
+    }
+    let before = lazy.expected_performance_at(start).success_rate.as_f64();
+    let after = lazy.expected_performance_at(start + Duration::from_secs(30));
+    assert_within(ticks, after.success_rate.as_f64(), 1e-6);
+    assert!(before < ticks);
+    // reads don't modify the performance
+    assert_eq!(
+        before,
+        lazy.expected_performance_at(start).success_rate.as_f64()
+    );
+    lazy.decay_until(start + Duration::from_secs(30));
+    assert_within(
+        ticks,
+        lazy.expected_performance().success_rate.as_f64(),
+        1e-6,
+    );
+    // reads match the performance once decayed
+    let decayed = lazy.expected_performance();
+    assert_within(
+        after.success_rate.as_f64(),
+        decayed.success_rate.as_f64(),
+        1e-6,
+    );
+    assert_eq!(after.latency_ms, decayed.latency_ms);
+}
+
+#[test]
+fn performance_config() {
+    assert_eq!(Ok(()), PerformanceConfig::default().validate());
+    let invalid = PerformanceConfig {
+        slow_decay_hz: 1.0,
+        ..Default::default()
+    };
+    assert_eq!(Err(InvalidPerformanceConfig::DecayHz), invalid.validate());
+
+    let fast = PerformanceConfig {
+        fast_bias: 1.0,
+        fast_decay_hz: 0.5,
+        max_success_rate: 1.0,
+        ..Default::default()
+    };
+    let mut perfs = [Performance::default(), Performance::new(fast)];
+    for perf in &mut perfs {
+        for _ in 0..100 {
+            perf.feedback(false, 100);
+        }
+        perf.decay(Duration::from_secs(10));
+        for _ in 0..20 {
+            perf.feedback(true, 100);
+        }
+    }
+    let [slow, fast] = perfs.map(|p| p.expected_performance().success_rate.as_f64());
+    assert!(slow < 0.5);
+    assert!(fast > 0.9);
+}
+
+#[test]
+fn failure_kinds() {
+    let mut perf = Performance::default();
+    let mut shim = Performance::default();
+    for i in 0..100 {
+        let outcome = match i % 4 {
+            0 => Outcome::Failure(FailureKind::BadQuery),
+            1 => Outcome::Failure(FailureKind::Timeout),
+            _ => Outcome::Success,
+        };
+        perf.feedback_outcome(outcome, 100);
+        if i % 4 != 0 {
+            shim.feedback(i % 4 != 1, 100);
+        }
+    }
+    assert_eq!(25.0, perf.failure_count(FailureKind::BadQuery));
+    assert_eq!(25.0, perf.failure_count(FailureKind::Timeout));
+    assert_eq!(0.0, perf.failure_count(FailureKind::Other));
+    assert_eq!(25.0, shim.failure_count(FailureKind::Other));
+    // bad queries count as neither successes nor failures
+    assert_eq!(
+        shim.expected_performance().success_rate,
+        perf.expected_performance().success_rate
+    );
+
+    let strict = PerformanceConfig {
+        failure_weights: FailureWeights {
+            bad_query: 1.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut strict = Performance::new(strict);
+    for i in 0..100 {
+        let outcome = match i % 4 {
+            0 => Outcome::Failure(FailureKind::BadQuery),
+            1 => Outcome::Failure(FailureKind::Timeout),
+            _ => Outcome::Success,
+        };
+        strict.feedback_outcome(outcome, 100);
+    }
+    assert!(strict.expected_performance().success_rate < perf.expected_performance().success_rate);
+
+    let invalid = PerformanceConfig {
+        failure_weights: FailureWeights {
+            stale: 1.5,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(
+        Err(InvalidPerformanceConfig::FailureWeight),
+        invalid.validate()
+    );
+}
+
+mod merge {
+    use proptest::{prelude::*, prop_assert_eq};
+
+    use super::*;
+
+    fn delta(responses: &[(bool, u16)]) -> PerformanceDelta {
+        let mut perf = Performance::default();
+        for (success, latency_ms) in responses {
+            perf.feedback(*success, *latency_ms);
+        }
+        perf.export_delta()
+    }
+
+    fn responses() -> impl Strategy<Value = Vec<(bool, u16)>> {
+        prop::collection::vec((any::<bool>(), 0..=30_000_u16), 0..50)
+    }
+
+    #[test]
+    fn replicas_converge() {
+        let mut a = Performance::default();
+        let mut b = Performance::default();
+        let mut all = Performance::default();
+        for i in 0..100 {
+            let replica = if i % 3 == 0 { &mut a } else { &mut b };
+            replica.feedback(i % 5 != 0, 50 + i);
+            all.feedback(i % 5 != 0, 50 + i);
+        }
+        let (delta_a, delta_b) = (a.export_delta(), b.export_delta());
+        a.merge(&delta_b);
+        b.merge(&delta_a);
+        // deltas from merged responses are empty, so nothing is counted twice
+        assert_eq!(PerformanceDelta::default(), a.export_delta());
+        assert_eq!(PerformanceDelta::default(), b.export_delta());
+        all.export_delta();
+        assert!(all.same_responses(&a));
+        assert!(all.same_responses(&b));
+    }
+
+    #[test]
+    fn pending_decay() {
+        let now = Instant::now();
+        let delta = delta(&[(true, 100), (false, 200)]);
+        let mut lazy = Performance::default();
+        let mut eager = Performance::default();
+        for perf in [&mut lazy, &mut eager] {
+            for i in 0..100 {
+                perf.feedback(i % 4 != 0, 100 + i);
+            }
+        }
+        lazy.decay_until(now);
+        lazy.merge_at(now + Duration::from_secs(60), &delta);
+        eager.decay(Duration::from_secs(60));
+        eager.merge(&delta);
+        assert!(lazy.same_responses(&eager));
+    }
+
+    proptest! {
+        #[test]
+        fn commutative(
+            base in responses(),
+            a in responses(),
+            b in responses(),
+        ) {
+            let (base, a, b) = (delta(&base), delta(&a), delta(&b));
+            let mut ab = Performance::default();
+            ab.merge(&base);
+            let mut ba = ab.clone();
+            ab.merge(&a);
+            ab.merge(&b);
+            ba.merge(&b);
+            ba.merge(&a);
+            prop_assert!(ab.same_responses(&ba));
+        }
+
+        #[test]
+        fn associative(
+            a in responses(),
+            b in responses(),
+            c in responses(),
+        ) {
+            let (a, b, c) = (delta(&a), delta(&b), delta(&c));
+            let mut ab_c = a.clone();
+            ab_c.merge(&b);
+            ab_c.merge(&c);
+            let mut bc = b.clone();
+            bc.merge(&c);
+            let mut a_bc = a.clone();
+            a_bc.merge(&bc);
+            prop_assert_eq!(&ab_c, &a_bc);
+
+            let mut left = Performance::default();
+            left.merge(&a);
+            left.merge(&b);
+            left.merge(&c);
+            let mut right = Performance::default();
+            right.merge(&a_bc);
+            prop_assert!(left.same_responses(&right));
+        }
+    }
+}
+
+mod snapshot {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn performance() -> Performance {
+        let mut perf = Performance::default();
+        for i in 0..100 {
+            perf.feedback(i % 4 != 0, 100 + i);
+        }
+        perf
+    }
+
+    #[test]
+    fn restore() {
+        let now = SystemTime::now();
+        let snapshot = performance().snapshot(now, Instant::now());
+        let restored = snapshot.restore(now + Duration::from_secs(60));
+        let mut expected = performance();
+        expected.decay(Duration::from_secs(60));
+        assert_eq!(
+            expected.expected_performance().success_rate,
+            restored.expected_performance().success_rate
+        );
+        assert_eq!(expected.sample_count(), restored.sample_count());
+        assert!(restored.sample_count() < performance().sample_count());
+    }
+
+    #[test]
+    fn pending_decay() {
+        let (now, instant) = (SystemTime::now(), Instant::now());
+        let mut lazy = performance();
+        lazy.decay_until(instant);
+        let restored = lazy
+            .snapshot(now, instant + Duration::from_secs(60))
+            .restore(now);
+        let mut expected = performance();
+        expected.decay(Duration::from_secs(60));
+        assert_eq!(expected.sample_count(), restored.sample_count());
+        // The decay of the histogram counts is rounded to `f32` when applied.
+        assert_within(
+            lazy.expected_performance_at(instant + Duration::from_secs(60))
+                .success_rate
+                .as_f64(),
+            restored.expected_performance().success_rate.as_f64(),
+            1e-6,
+        );
+        assert!(restored.sample_count() < performance().sample_count());
+    }
+
+    #[test]
+    fn unshared() {
+        let now = SystemTime::now();
+        let mut restored = performance().snapshot(now, Instant::now()).restore(now);
+        assert!(performance().same_responses(&restored));
+        assert_eq!(PerformanceDelta::default(), restored.export_delta());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let now = SystemTime::now();
+        let json = serde_json::to_string(&performance().snapshot(now, Instant::now())).unwrap();
+        let snapshot: PerformanceSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(now, snapshot.taken_at);
+        let restored = snapshot.restore(now);
+        assert_eq!(
+            performance().expected_performance().latency_ms,
+            restored.expected_performance().latency_ms
+        );
+
+        let json = serde_json::to_value(performance()).unwrap();
+        assert_eq!("2", json["version"]);
+        assert!(json.get("unshared").is_none());
+        let mut json = json;
+        json["version"] = "0".into();
+        assert!(serde_json::from_value::<Performance>(json).is_err());
+
+        // V1 has no failure kinds or weights, and its failures migrate as `FailureKind::Other`
+        let mut v1 = serde_json::to_value(performance()).unwrap();
+        v1["version"] = "1".into();
+        v1["slow"].as_object_mut().unwrap().remove("failure_kinds");
+        v1["config"]
+            .as_object_mut()
+            .unwrap()
+            .remove("failure_weights");
+        let migrated: Performance = serde_json::from_value(v1.clone()).unwrap();
+        assert!(performance().same_responses(&migrated));
+        assert_eq!(25.0, migrated.failure_count(FailureKind::Other));
+        assert_eq!("2", serde_json::to_value(&migrated).unwrap()["version"]);
+        v1["version"] = "2".into();
+        assert!(serde_json::from_value::<Performance>(v1).is_err());
+
+        // invalid configs are rejected, including when restoring a snapshot
+        let invalid = r#"{"fast_decay_hz": 1.5}"#;
+        let err = serde_json::from_str::<PerformanceConfig>(invalid).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&InvalidPerformanceConfig::DecayHz.to_string()));
+        let mut json = serde_json::to_value(performance().snapshot(now, Instant::now())).unwrap();
+        json["performance"]["config"]["latency_percentile"] = 100.into();
+        assert!(serde_json::from_value::<PerformanceSnapshot>(json).is_err());
+        let json = serde_json::to_string(&PerformanceConfig::DEFAULT).unwrap();
+        assert_eq!(
+            PerformanceConfig::DEFAULT,
+            serde_json::from_str(&json).unwrap()
+        );
+    }
+}
+
+#[test]
+fn score_breakdown() {
+    let candidates = [
+        Candidate {
+            id: 0,
+            data: (),
+            perf: ExpectedPerformance {
+                success_rate: Normalized::new(0.99).unwrap(),
+                latency_ms: 100,
+            },
+            fee: BUDGET / 2,
+            seconds_behind: 86400,
+            slashable_grt: 1_000_000,
+            group: None,
+        },
+        Candidate {
+            id: 1,
+            data: (),
+            perf: ExpectedPerformance {
+                success_rate: Normalized::new(0.99).unwrap(),
+                latency_ms: 200,
+            },
+            fee: BUDGET * 6 / 10,
+            seconds_behind: 0,
+            slashable_grt: 1_000_000,
+            group: None,
+        },
+    ];
+
+    let breakdown = candidates[0].score_breakdown();
+    assert_eq!(candidates[0].score(), breakdown.combined);
+    let worst = [
+        breakdown.success_rate,
+        breakdown.latency,
+        breakdown.slashable_grt,
+    ];
+    assert!(worst.iter().all(|s| breakdown.seconds_behind < *s));
+
+    let set: ArrayVec<&Candidate<u64, ()>, 3> = candidates.iter().collect();
+    assert_eq!(None, Candidate::score_many_breakdown::<3>(&set, BUDGET));
+    assert_eq!(Normalized::ZERO, score_many(&set));
+
+    let set: ArrayVec<&Candidate<u64, ()>, 3> = candidates.iter().skip(1).collect();
+    let breakdown = Candidate::score_many_breakdown::<3>(&set, BUDGET).unwrap();
+    assert_eq!(score_many(&set), breakdown.combined);
+    assert_eq!(
+        Some(breakdown),
+        Candidate::score_many_breakdown_dyn(&set, BUDGET)
+    );
+}